@@ -0,0 +1,136 @@
+use serde::Serialize;
+
+use crate::{
+    search_results_response::SearchResultsResponse,
+    utils::google2005_error::Google2005Error,
+};
+
+/// Version of the `/api/search` document. Bump it when a field is removed
+/// or changes meaning; adding fields is not a breaking change.
+pub const API_VERSION: u16 = 1;
+
+/// The JSON document served from `/api/search`: the filtered results page,
+/// or the error that prevented building one.
+#[derive(Debug, Serialize)]
+pub struct ApiResponse<'a> {
+    version: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    search: Option<&'a SearchResultsResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ApiError>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct ApiError {
+    code: &'static str,
+    status_code: u16,
+    status: String,
+    message: String,
+}
+
+impl<'a> ApiResponse<'a> {
+    pub fn new(
+        result: &'a Result<SearchResultsResponse, Google2005Error>,
+    ) -> ApiResponse<'a> {
+        match result {
+            Ok(search) => ApiResponse {
+                version: API_VERSION,
+                search: Some(search),
+                error: None,
+            },
+            Err(e) => ApiResponse {
+                version: API_VERSION,
+                search: None,
+                error: Some(ApiError {
+                    code: e.code(),
+                    status_code: e.status_code(),
+                    status: e.status().to_string(),
+                    message: e.to_string(),
+                }),
+            },
+        }
+    }
+
+    pub fn status_code(&self) -> u16 {
+        match &self.error {
+            Some(error) => error.status_code,
+            None => 200,
+        }
+    }
+
+    /// Reason phrase to go with `status_code`.
+    pub fn status(&self) -> &str {
+        match &self.error {
+            Some(error) => &error.status,
+            None => "OK",
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self)
+            .expect("api responses only contain serializable fields")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::search_request::SearchRequest;
+
+    #[test]
+    fn test_error_document() {
+        let result = Err(Google2005Error::bad_query("Please enter a query"));
+        let response = ApiResponse::new(&result);
+
+        assert_eq!(response.status_code(), 400);
+        assert_eq!(response.status(), "Bad Request");
+        assert_eq!(
+            response.to_json(),
+            concat!(
+                r#"{"version":1,"error":{"code":"bad_query","#,
+                r#""status_code":400,"#,
+                r#""status":"Bad Request","message":"Please enter a query"}}"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_error_document_hides_internal_message() {
+        let result = Err(Google2005Error::parse_failed("backend timed out"));
+        let json = ApiResponse::new(&result).to_json();
+
+        assert!(!json.contains("backend timed out"));
+        assert!(json.contains(r#""code":"parse_failed","status_code":502"#));
+    }
+
+    #[test]
+    fn test_search_document() {
+        let page = concat!(
+            "<html><head><title>cubs - Google Search</title></head><body>",
+            r#"<a href="https://www.mlb.com/cubs"><h3>Chicago Cubs</h3></a>"#,
+            "<span>The official site of the Chicago Cubs</span>",
+            "</body></html>"
+        );
+        let request = SearchRequest::parse("q=cubs&start=10").unwrap();
+        let result = crate::scrape(&request, page);
+        let response = ApiResponse::new(&result);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&response.to_json()).unwrap();
+
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["search"]["query"], "cubs");
+        assert_eq!(json["search"]["page"], 2);
+        assert_eq!(json["search"]["first_result"], 10);
+        assert_eq!(json["search"]["last_result"], 11);
+        assert_eq!(
+            json["search"]["results"][0]["url"],
+            "https://www.mlb.com/cubs"
+        );
+        assert_eq!(json["search"]["health"]["kept"], 1);
+        assert_eq!(json["search"]["health"]["healthy"], false);
+        assert!(json["search"].get("image_hostname").is_none());
+        assert!(json.get("error").is_none());
+    }
+}
@@ -0,0 +1,392 @@
+use scraper::Html;
+use serde::Serialize;
+
+use crate::{
+    fetcher::SearchFetcher,
+    search_engine_parser::{PageMetadata, ParserChoice},
+    search_request::{SearchRequest, MAX_START},
+    search_result::{SearchResult, SearchResultOwned},
+    search_results::{FilterVerdict, PINNED},
+    utils::google2005_error::Google2005Error,
+};
+
+/// Results on each of our pages.
+pub const RESULTS_PER_PAGE: usize = 10;
+
+/// Upstream pages each of our pages may be read from: the first always,
+/// the others only to make up for results the filter dropped.
+pub const UPSTREAM_PAGES_PER_PAGE: u16 = 2;
+
+// Results on each of Google's pages when the request doesn't set `num`.
+const UPSTREAM_PAGE_SIZE: u16 = 10;
+
+/// A place in Google's results: the upstream page at `start`, after the
+/// first `skip` results parsed from it. Our links to a page carry its cursor
+/// (`ustart` and `uskip`) so each page starts where the one before it
+/// stopped, leaving no upstream result out. Only the first page, the current
+/// one and the next have a known cursor, so those are the pages linked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct UpstreamCursor {
+    pub start: u16,
+    pub skip: u16,
+}
+
+impl UpstreamCursor {
+    /// Where our page for `request` starts reading: its cursor, or for a
+    /// page reached without one, Google's page at the same `start`.
+    pub fn of(request: &SearchRequest) -> UpstreamCursor {
+        request.upstream.unwrap_or(UpstreamCursor {
+            start: request.start,
+            skip: 0,
+        })
+    }
+
+    /// The cursor as query string parameters, starting with `&`.
+    pub fn params(&self) -> String {
+        format!("&ustart={}&uskip={}", self.start, self.skip)
+    }
+}
+
+/// The upstream `start` offsets of the pages our page for `request` may
+/// be read from, `num` results apart and none past `MAX_START`.
+pub fn upstream_starts(request: &SearchRequest) -> Vec<u16> {
+    let first = UpstreamCursor::of(request).start;
+    let page_size = request.num.unwrap_or(UPSTREAM_PAGE_SIZE);
+
+    (0..UPSTREAM_PAGES_PER_PAGE)
+        .map(|i| first.saturating_add(i.saturating_mul(page_size)))
+        .filter(|start| *start <= MAX_START)
+        .collect()
+}
+
+/// The parsed upstream pages behind one of our pages.
+#[derive(Debug)]
+pub struct UpstreamWindow {
+    /// Every result on the pages read, in order, before filtering.
+    pub results: Vec<SearchResultOwned>,
+    /// Where each of `results` was read from.
+    pub positions: Vec<UpstreamCursor>,
+    /// Just past the last page read.
+    pub end: UpstreamCursor,
+    /// What the parser saw of the first page.
+    pub metadata: PageMetadata,
+}
+
+impl UpstreamWindow {
+    /// Reads the upstream pages for our page of `request` until `kept`
+    /// says the results read so far fill it. Failing to read the first
+    /// page fails the search; failing to read a later one only ends the
+    /// backfill.
+    pub fn read(
+        request: &SearchRequest,
+        fetcher: &dyn SearchFetcher,
+        parser: ParserChoice,
+        kept: impl Fn(&[SearchResultOwned]) -> usize,
+    ) -> Result<UpstreamWindow, Google2005Error> {
+        let cursor = UpstreamCursor::of(request);
+        let mut upstream_starts = upstream_starts(request).into_iter();
+        let first = upstream_starts.next().unwrap_or(cursor.start);
+
+        // the results the page before ours already covered
+        let mut window = Self::read_page(request, first, fetcher, parser)?;
+        let skip = (cursor.skip as usize).min(window.results.len());
+        window.results.drain(..skip);
+        window.positions.drain(..skip);
+
+        for upstream_start in upstream_starts {
+            if kept(&window.results) >= RESULTS_PER_PAGE {
+                break;
+            }
+
+            match Self::read_page(request, upstream_start, fetcher, parser) {
+                Ok(page) => {
+                    window.results.extend(page.results);
+                    window.positions.extend(page.positions);
+                    window.end = page.end;
+                }
+                Err(e) => {
+                    println!(
+                        "backfill from {} failed: {}",
+                        request.upstream_url(upstream_start),
+                        e.details()
+                    );
+                    break;
+                }
+            }
+        }
+
+        Ok(window)
+    }
+
+    /// Where the page after ours starts: at the first result the filter
+    /// kept that didn't fit on our page, or past everything read. None when
+    /// that's past the last page Google serves. `verdicts` are the filter's,
+    /// one for each of `results`.
+    pub fn next_page(
+        &self,
+        verdicts: &[FilterVerdict],
+    ) -> Option<UpstreamCursor> {
+        let kept = verdicts
+            .iter()
+            .enumerate()
+            .filter(|(_, verdict)| verdict.kept)
+            .collect::<Vec<_>>();
+
+        // pinned results are shown first
+        let mut shown = kept.clone();
+        shown.sort_by_key(|(_, verdict)| verdict.rule != PINNED);
+        shown.truncate(RESULTS_PER_PAGE);
+
+        let next = kept
+            .iter()
+            .map(|(i, _)| *i)
+            .find(|i| !shown.iter().any(|(shown, _)| shown == i))
+            .and_then(|i| self.positions.get(i).copied())
+            .unwrap_or(self.end);
+
+        Some(next).filter(|next| next.start <= MAX_START)
+    }
+
+    fn read_page(
+        request: &SearchRequest,
+        start: u16,
+        fetcher: &dyn SearchFetcher,
+        parser: ParserChoice,
+    ) -> Result<UpstreamWindow, Google2005Error> {
+        let url = request.upstream_url(start);
+        let results_page = fetcher.fetch(&url)?.into_body()?;
+        let dom = Html::parse_document(&results_page);
+        let page = parser.select(&dom).parse(&dom)?;
+        let page_size = request.num.unwrap_or(UPSTREAM_PAGE_SIZE);
+
+        Ok(UpstreamWindow {
+            positions: (0..page.results.len())
+                .map(|skip| UpstreamCursor {
+                    start,
+                    skip: skip as u16,
+                })
+                .collect(),
+            results: page
+                .results
+                .into_iter()
+                .map(SearchResult::into_owned)
+                .collect(),
+            end: UpstreamCursor {
+                start: start.saturating_add(page_size),
+                skip: 0,
+            },
+            metadata: page.metadata,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::RefCell;
+
+    use crate::fetcher::FetchedPage;
+    use crate::{FilterRules, Preferences};
+
+    const FIRST_PAGE: &str = "https://www.google.com/search?q=cubs";
+    const SECOND_PAGE: &str = "https://www.google.com/search?q=cubs&start=10";
+    const THIRD_PAGE: &str = "https://www.google.com/search?q=cubs&start=20";
+
+    // Serves a page with a result for each of `sites` at each URL, and
+    // remembers the URLs it was asked for.
+    struct Pages {
+        pages: Vec<(&'static str, Option<Vec<String>>)>,
+        fetched: RefCell<Vec<String>>,
+    }
+
+    impl Pages {
+        fn new(pages: Vec<(&'static str, Option<Vec<String>>)>) -> Pages {
+            Pages {
+                pages,
+                fetched: RefCell::new(vec![]),
+            }
+        }
+    }
+
+    impl SearchFetcher for Pages {
+        fn fetch(&self, url: &str) -> Result<FetchedPage, Google2005Error> {
+            self.fetched.borrow_mut().push(url.to_string());
+
+            let sites = self
+                .pages
+                .iter()
+                .find(|(page_url, _)| *page_url == url)
+                .map(|(_, sites)| sites.as_ref());
+
+            match sites {
+                Some(Some(sites)) => Ok(FetchedPage::new(200, page(sites))),
+                _ => Ok(FetchedPage::new(429, "Too Many Requests")),
+            }
+        }
+    }
+
+    fn page(sites: &[String]) -> String {
+        let results = sites
+            .iter()
+            .map(|site| {
+                format!(
+                    concat!(
+                        r#"<a href="https://{0}/"><h3>{0}</h3></a>"#,
+                        "<span>{0}</span>"
+                    ),
+                    site
+                )
+            })
+            .collect::<String>();
+
+        format!(
+            "<html><head><title>cubs - Google Search</title></head>\
+             <body>{}</body></html>",
+            results
+        )
+    }
+
+    fn sites(range: std::ops::Range<usize>) -> Option<Vec<String>> {
+        Some(range.map(|i| format!("site{}.com", i)).collect())
+    }
+
+    fn search_page(fetcher: &Pages, query_string: &str) -> serde_json::Value {
+        let response = crate::search(
+            &SearchRequest::parse(query_string).unwrap(),
+            fetcher,
+            &FilterRules::default(),
+            &Preferences::default(),
+        )
+        .unwrap();
+
+        serde_json::to_value(&response).unwrap()
+    }
+
+    fn urls(response: &serde_json::Value) -> Vec<String> {
+        response["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| result["url"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    fn search(fetcher: &Pages) -> Vec<String> {
+        urls(&search_page(fetcher, "q=cubs"))
+    }
+
+    fn site_urls(range: std::ops::Range<usize>) -> Vec<String> {
+        range.map(|i| format!("https://site{}.com/", i)).collect()
+    }
+
+    fn starts(query_string: &str) -> Vec<u16> {
+        upstream_starts(&SearchRequest::parse(query_string).unwrap())
+    }
+
+    #[test]
+    fn test_upstream_starts() {
+        assert_eq!(starts("q=cubs"), vec![0, 10]);
+        assert_eq!(starts("q=cubs&start=10"), vec![10, 20]);
+        assert_eq!(starts("q=cubs&start=10&ustart=13&uskip=2"), vec![13, 23]);
+        assert_eq!(starts("q=cubs&start=20&num=20"), vec![20, 40]);
+        assert_eq!(starts("q=cubs&start=990"), vec![990]);
+    }
+
+    #[test]
+    fn test_no_next_page_past_last_start() {
+        let last_page = "https://www.google.com/search?q=cubs&start=990";
+        let fetcher = Pages::new(vec![(last_page, sites(0..10))]);
+
+        let response = search_page(&fetcher, "q=cubs&start=990");
+
+        assert_eq!(urls(&response).len(), 10);
+        assert_eq!(response["next_upstream"], serde_json::Value::Null);
+        assert_eq!(response["next_page_starts"], serde_json::json!([0, 990]));
+    }
+
+    #[test]
+    fn test_full_page_is_not_backfilled() {
+        let fetcher = Pages::new(vec![(FIRST_PAGE, sites(0..10))]);
+
+        let response = search_page(&fetcher, "q=cubs");
+
+        assert_eq!(urls(&response).len(), 10);
+        assert_eq!(*fetcher.fetched.borrow(), vec![FIRST_PAGE]);
+        // Google's second page is next, not skipped
+        assert_eq!(
+            response["next_upstream"],
+            serde_json::json!({ "start": 10, "skip": 0 })
+        );
+    }
+
+    #[test]
+    fn test_backfills_from_next_page() {
+        // the second page repeats two of the first page's results
+        let fetcher = Pages::new(vec![
+            (FIRST_PAGE, sites(0..6)),
+            (SECOND_PAGE, sites(4..12)),
+        ]);
+
+        assert_eq!(
+            search(&fetcher),
+            sites(0..10)
+                .unwrap()
+                .iter()
+                .map(|site| format!("https://{}/", site))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(*fetcher.fetched.borrow(), vec![FIRST_PAGE, SECOND_PAGE]);
+    }
+
+    #[test]
+    fn test_next_page_starts_at_first_result_not_shown() {
+        let fetcher = Pages::new(vec![
+            (FIRST_PAGE, sites(0..6)),
+            (SECOND_PAGE, sites(4..12)),
+            (THIRD_PAGE, sites(12..20)),
+        ]);
+
+        let first = search_page(&fetcher, "q=cubs");
+        assert_eq!(urls(&first), site_urls(0..10));
+        // site10.com, the 7th result read from Google's second page
+        assert_eq!(
+            first["next_upstream"],
+            serde_json::json!({ "start": 10, "skip": 6 })
+        );
+
+        let second =
+            search_page(&fetcher, "q=cubs&start=10&ustart=10&uskip=6");
+        assert_eq!(urls(&second), site_urls(10..20));
+        assert_eq!(
+            second["next_upstream"],
+            serde_json::json!({ "start": 30, "skip": 0 })
+        );
+    }
+
+    #[test]
+    fn test_reads_pages_of_num_results() {
+        let fetcher = Pages::new(vec![
+            ("https://www.google.com/search?q=cubs&num=20", sites(0..3)),
+            (
+                "https://www.google.com/search?q=cubs&num=20&start=20",
+                sites(3..20),
+            ),
+        ]);
+
+        let response = search_page(&fetcher, "q=cubs&num=20");
+
+        assert_eq!(urls(&response), site_urls(0..10));
+        assert_eq!(
+            response["next_upstream"],
+            serde_json::json!({ "start": 20, "skip": 7 })
+        );
+    }
+
+    #[test]
+    fn test_failed_backfill_keeps_first_page() {
+        let fetcher =
+            Pages::new(vec![(FIRST_PAGE, sites(0..6)), (SECOND_PAGE, None)]);
+
+        assert_eq!(search(&fetcher).len(), 6);
+    }
+}
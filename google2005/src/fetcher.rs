@@ -0,0 +1,149 @@
+use std::path::PathBuf;
+
+use crate::utils::google2005_error::Google2005Error;
+
+#[cfg(feature = "fastly")]
+mod fastly_backend;
+mod file;
+mod fixture;
+#[cfg(feature = "reqwest")]
+mod http;
+#[cfg(any(feature = "fastly", feature = "reqwest"))]
+mod lambda;
+
+#[cfg(feature = "fastly")]
+pub use fastly_backend::FastlyBackendFetcher;
+pub use file::FileFetcher;
+pub use fixture::{Fixture, RecordingFetcher, ReplayFetcher};
+#[cfg(feature = "reqwest")]
+pub use http::HttpFetcher;
+#[cfg(feature = "reqwest")]
+pub use lambda::LambdaFetcher;
+
+// Google serves its plain, no-javascript results page to anything it doesn't
+// recognise as a browser, so fetchers claim to be one.
+#[cfg(any(feature = "fastly", feature = "reqwest"))]
+pub(crate) const USER_AGENT: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/102.0.0.0 Safari/537.36";
+
+/// Gets the results page for a Google search URL, from wherever it comes
+/// from: Google itself, a proxy in front of it, or a saved copy.
+pub trait SearchFetcher {
+    fn fetch(&self, url: &str) -> Result<FetchedPage, Google2005Error>;
+}
+
+/// A response to a search URL, whatever its status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchedPage {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl FetchedPage {
+    pub fn new(status: u16, body: impl Into<String>) -> FetchedPage {
+        FetchedPage {
+            status,
+            headers: vec![],
+            body: body.into(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> FetchedPage {
+        self.headers.push((name.to_string(), value.to_string()));
+
+        self
+    }
+
+    /// The page, if the search engine answered with a success status.
+    pub fn into_body(self) -> Result<String, Google2005Error> {
+        match self.status {
+            200..=299 => Ok(self.body),
+            status => Err(Google2005Error::UpstreamStatus(status)),
+        }
+    }
+}
+
+/// Which fetcher an executable should use, as named in its configuration:
+/// `direct`, `lambda`, `file:<path>`, `record:<dir>` or `replay:<dir>`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FetcherConfig {
+    /// Request the results page from Google.
+    Direct,
+    /// Have the google2005 lambda request it from Google for us.
+    Lambda,
+    /// Serve the page saved at the path for every search.
+    File(PathBuf),
+    /// Request pages from Google, saving each one as a fixture in the
+    /// directory.
+    Record(PathBuf),
+    /// Serve the fixtures saved in the directory.
+    Replay(PathBuf),
+}
+
+impl FetcherConfig {
+    /// Environment variable native executables read the setting from.
+    pub const ENV_VAR: &'static str = "GOOGLE2005_FETCHER";
+
+    pub fn parse(value: &str) -> Option<FetcherConfig> {
+        match value.trim() {
+            "direct" => Some(FetcherConfig::Direct),
+            "lambda" => Some(FetcherConfig::Lambda),
+            value => {
+                let (kind, path) = value.split_once(':')?;
+                let path = PathBuf::from(path);
+
+                match kind {
+                    "file" => Some(FetcherConfig::File(path)),
+                    "record" => Some(FetcherConfig::Record(path)),
+                    "replay" => Some(FetcherConfig::Replay(path)),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// The configured fetcher, or `None` when `ENV_VAR` is unset or names
+    /// no fetcher.
+    pub fn from_env() -> Option<FetcherConfig> {
+        let value = std::env::var(Self::ENV_VAR).ok()?;
+        let config = Self::parse(&value);
+
+        if config.is_none() {
+            println!("Unknown {}: {:?}", Self::ENV_VAR, value);
+        }
+
+        config
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_into_body() {
+        assert_eq!(
+            FetchedPage::new(200, "<html></html>").into_body().unwrap(),
+            "<html></html>"
+        );
+        assert!(matches!(
+            FetchedPage::new(429, "slow down").into_body(),
+            Err(Google2005Error::UpstreamStatus(429))
+        ));
+    }
+
+    #[test]
+    fn test_parse_config() {
+        assert_eq!(FetcherConfig::parse("direct"), Some(FetcherConfig::Direct));
+        assert_eq!(FetcherConfig::parse("lambda"), Some(FetcherConfig::Lambda));
+        assert_eq!(
+            FetcherConfig::parse("file:fixtures/cubs.html"),
+            Some(FetcherConfig::File(PathBuf::from("fixtures/cubs.html")))
+        );
+        assert_eq!(
+            FetcherConfig::parse("replay:fixtures"),
+            Some(FetcherConfig::Replay(PathBuf::from("fixtures")))
+        );
+        assert_eq!(FetcherConfig::parse("carrier-pigeon"), None);
+    }
+}
@@ -0,0 +1,62 @@
+use fastly::Request;
+
+use super::lambda::{request_body, LAMBDA_HOST, LAMBDA_URL};
+use super::{FetchedPage, SearchFetcher, USER_AGENT};
+use crate::utils::google2005_error::Google2005Error;
+
+/// Fetches search URLs through a Fastly backend, either from Google itself
+/// or through the google2005 lambda.
+#[derive(Debug, Clone)]
+pub struct FastlyBackendFetcher {
+    backend: String,
+    through_lambda: bool,
+}
+
+impl FastlyBackendFetcher {
+    /// `backend` must point at Google.
+    pub fn direct(backend: &str) -> FastlyBackendFetcher {
+        FastlyBackendFetcher {
+            backend: backend.to_string(),
+            through_lambda: false,
+        }
+    }
+
+    /// `backend` must point at the lambda's API Gateway.
+    pub fn lambda(backend: &str) -> FastlyBackendFetcher {
+        FastlyBackendFetcher {
+            backend: backend.to_string(),
+            through_lambda: true,
+        }
+    }
+}
+
+impl SearchFetcher for FastlyBackendFetcher {
+    fn fetch(&self, url: &str) -> Result<FetchedPage, Google2005Error> {
+        let request = if self.through_lambda {
+            Request::post(LAMBDA_URL)
+                .with_header("Content-Type", "application/json")
+                .with_header("Accept", "*/*")
+                .with_header("Host", LAMBDA_HOST)
+                .with_body(request_body(url))
+        } else {
+            Request::get(url)
+        };
+
+        let mut response = request
+            .with_header("User-Agent", USER_AGENT)
+            .send(self.backend.as_str())?;
+        let body = String::from_utf8(response.take_body().into_bytes())?;
+        let headers = response
+            .get_headers()
+            .filter_map(|(name, value)| {
+                Some((name.to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect();
+
+        Ok(FetchedPage {
+            status: response.get_status().as_u16(),
+            headers,
+            body,
+        })
+    }
+}
@@ -0,0 +1,55 @@
+use std::fs;
+use std::path::PathBuf;
+
+use super::{FetchedPage, SearchFetcher};
+use crate::utils::google2005_error::Google2005Error;
+
+/// Answers every search with a results page saved on disk, for working
+/// offline and for tests.
+#[derive(Debug, Clone)]
+pub struct FileFetcher {
+    path: PathBuf,
+}
+
+impl FileFetcher {
+    pub fn new(path: impl Into<PathBuf>) -> FileFetcher {
+        FileFetcher { path: path.into() }
+    }
+}
+
+impl SearchFetcher for FileFetcher {
+    fn fetch(&self, _url: &str) -> Result<FetchedPage, Google2005Error> {
+        let body = fs::read_to_string(&self.path)
+            .map_err(Google2005Error::upstream_unavailable)?;
+
+        Ok(FetchedPage::new(200, body))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reads_page() {
+        let path = std::env::temp_dir().join("google2005_file_fetcher.html");
+        fs::write(&path, "<html></html>").unwrap();
+
+        let page = FileFetcher::new(&path)
+            .fetch("https://www.google.com/search?q=cubs")
+            .unwrap();
+
+        assert_eq!(page, FetchedPage::new(200, "<html></html>"));
+    }
+
+    #[test]
+    fn test_missing_page() {
+        let result = FileFetcher::new("/nonexistent/google2005.html")
+            .fetch("https://www.google.com/search?q=cubs");
+
+        assert!(matches!(
+            result,
+            Err(Google2005Error::UpstreamUnavailable(_))
+        ));
+    }
+}
@@ -0,0 +1,244 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{FetchedPage, SearchFetcher};
+use crate::utils::{fnv::fnv1a, google2005_error::Google2005Error, timestamp};
+
+// Keeps fixture names readable in a directory listing.
+const MAX_SLUG_LENGTH: usize = 60;
+
+/// What's known about a saved results page besides its body, kept next to
+/// it as `<name>.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fixture {
+    pub url: String,
+    /// The search terms, decoded.
+    pub query: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// When the page was recorded. Synthetic pages never were.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetched_at: Option<String>,
+    /// Written by hand rather than recorded from Google, so its markup is
+    /// made up.
+    #[serde(default)]
+    pub synthetic: bool,
+}
+
+impl Fixture {
+    /// The file name, without extension, that the page for `url` is saved
+    /// under: a slug of its query and a hash of the whole URL.
+    pub fn name(url: &str) -> String {
+        let params = url.split_once('?').map_or(url, |(_, params)| params);
+        let params = params.strip_prefix("q=").unwrap_or(params);
+
+        let mut slug = String::new();
+        for c in params.chars().flat_map(char::to_lowercase) {
+            if c.is_ascii_alphanumeric() {
+                slug.push(c);
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+        slug.truncate(MAX_SLUG_LENGTH);
+
+        format!("{}-{:08x}", slug.trim_end_matches('-'), fnv1a(url))
+    }
+
+    fn paths(dir: &Path, url: &str) -> (PathBuf, PathBuf) {
+        let name = Self::name(url);
+
+        (
+            dir.join(format!("{}.html", name)),
+            dir.join(format!("{}.json", name)),
+        )
+    }
+
+    fn query(url: &str) -> String {
+        Url::parse(url)
+            .ok()
+            .and_then(|url| {
+                url.query_pairs()
+                    .find(|(key, _)| key == "q")
+                    .map(|(_, value)| value.into_owned())
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Passes searches on to another fetcher and saves every page it gets back
+/// into `dir`, for `ReplayFetcher` to serve later.
+pub struct RecordingFetcher<F> {
+    inner: F,
+    dir: PathBuf,
+}
+
+impl<F: SearchFetcher> RecordingFetcher<F> {
+    pub fn new(inner: F, dir: impl Into<PathBuf>) -> RecordingFetcher<F> {
+        RecordingFetcher {
+            inner,
+            dir: dir.into(),
+        }
+    }
+
+    fn record(&self, url: &str, page: &FetchedPage) -> io::Result<()> {
+        let (html_path, json_path) = Fixture::paths(&self.dir, url);
+        let fixture = Fixture {
+            url: url.to_string(),
+            query: Fixture::query(url),
+            status: page.status,
+            headers: page.headers.clone(),
+            fetched_at: Some(timestamp::rfc3339(SystemTime::now())),
+            synthetic: false,
+        };
+
+        fs::create_dir_all(&self.dir)?;
+        fs::write(&html_path, &page.body)?;
+        fs::write(json_path, serde_json::to_string_pretty(&fixture)? + "\n")?;
+
+        println!("recorded {} to {}", url, html_path.display());
+
+        Ok(())
+    }
+}
+
+impl<F: SearchFetcher> SearchFetcher for RecordingFetcher<F> {
+    fn fetch(&self, url: &str) -> Result<FetchedPage, Google2005Error> {
+        let page = self.inner.fetch(url)?;
+
+        // A failed recording shouldn't fail the search
+        if let Err(e) = self.record(url, &page) {
+            println!("could not record {}: {}", url, e);
+        }
+
+        Ok(page)
+    }
+}
+
+/// Serves the pages a `RecordingFetcher` saved into `dir`, so searches work
+/// without a network.
+#[derive(Debug, Clone)]
+pub struct ReplayFetcher {
+    dir: PathBuf,
+}
+
+impl ReplayFetcher {
+    pub fn new(dir: impl Into<PathBuf>) -> ReplayFetcher {
+        ReplayFetcher { dir: dir.into() }
+    }
+
+    /// Every saved fixture, with its page, ordered by name.
+    pub fn fixtures(&self) -> io::Result<Vec<(Fixture, String)>> {
+        let mut json_paths = fs::read_dir(&self.dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        json_paths.retain(|path| path.extension().is_some_and(|e| e == "json"));
+        json_paths.sort();
+
+        json_paths
+            .iter()
+            .map(|json_path| {
+                let fixture: Fixture =
+                    serde_json::from_str(&fs::read_to_string(json_path)?)?;
+                let body =
+                    fs::read_to_string(json_path.with_extension("html"))?;
+
+                Ok((fixture, body))
+            })
+            .collect()
+    }
+}
+
+impl SearchFetcher for ReplayFetcher {
+    fn fetch(&self, url: &str) -> Result<FetchedPage, Google2005Error> {
+        let (html_path, json_path) = Fixture::paths(&self.dir, url);
+
+        let missing = |e: io::Error| {
+            Google2005Error::upstream_unavailable(io::Error::new(
+                e.kind(),
+                format!("no fixture for {} at {}", url, html_path.display()),
+            ))
+        };
+
+        let body = fs::read_to_string(&html_path).map_err(missing)?;
+        let fixture: Fixture = fs::read_to_string(&json_path)
+            .map_err(missing)
+            .and_then(|json| {
+                serde_json::from_str(&json)
+                    .map_err(Google2005Error::upstream_unavailable)
+            })?;
+
+        Ok(FetchedPage {
+            status: fixture.status,
+            headers: fixture.headers,
+            body,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const URL: &str = "https://www.google.com/search?q=chicago+cubs&start=10";
+
+    struct Upstream;
+
+    impl SearchFetcher for Upstream {
+        fn fetch(&self, _url: &str) -> Result<FetchedPage, Google2005Error> {
+            Ok(FetchedPage::new(200, "<html>cubs</html>")
+                .with_header("Content-Type", "text/html; charset=UTF-8"))
+        }
+    }
+
+    fn fixtures_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("google2005_{}", test));
+        let _ = fs::remove_dir_all(&dir);
+
+        dir
+    }
+
+    #[test]
+    fn test_fixture_name() {
+        let name = Fixture::name(URL);
+
+        assert!(name.starts_with("chicago-cubs-start-10-"));
+        assert_ne!(
+            name,
+            Fixture::name(&URL.replace("start=10", "start=20"))
+        );
+    }
+
+    #[test]
+    fn test_replays_recorded_page() {
+        let dir = fixtures_dir("replays_recorded_page");
+
+        let recorded =
+            RecordingFetcher::new(Upstream, &dir).fetch(URL).unwrap();
+        let replayed = ReplayFetcher::new(&dir).fetch(URL).unwrap();
+
+        assert_eq!(replayed, recorded);
+
+        let fixtures = ReplayFetcher::new(&dir).fixtures().unwrap();
+        assert_eq!(fixtures.len(), 1);
+        assert_eq!(fixtures[0].0.query, "chicago cubs");
+        assert!(fixtures[0].0.fetched_at.is_some());
+        assert!(!fixtures[0].0.synthetic);
+        assert_eq!(fixtures[0].1, "<html>cubs</html>");
+    }
+
+    #[test]
+    fn test_replay_without_fixture() {
+        let dir = fixtures_dir("replay_without_fixture");
+
+        match ReplayFetcher::new(&dir).fetch(URL) {
+            Err(e) => assert!(e.details().contains("no fixture for")),
+            Ok(page) => panic!("replayed {:?}", page),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use super::{FetchedPage, SearchFetcher, USER_AGENT};
+use crate::utils::google2005_error::Google2005Error;
+
+/// Requests search URLs straight from Google.
+#[derive(Debug, Clone, Default)]
+pub struct HttpFetcher {
+    client: reqwest::blocking::Client,
+}
+
+impl HttpFetcher {
+    pub fn new() -> HttpFetcher {
+        HttpFetcher::default()
+    }
+}
+
+impl SearchFetcher for HttpFetcher {
+    fn fetch(&self, url: &str) -> Result<FetchedPage, Google2005Error> {
+        let response =
+            self.client.get(url).header("User-Agent", USER_AGENT).send()?;
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((name.to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect();
+
+        Ok(FetchedPage {
+            status,
+            headers,
+            body: response.text()?,
+        })
+    }
+}
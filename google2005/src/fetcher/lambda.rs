@@ -0,0 +1,72 @@
+#[cfg(feature = "reqwest")]
+use super::{FetchedPage, SearchFetcher, USER_AGENT};
+#[cfg(feature = "reqwest")]
+use crate::utils::google2005_error::Google2005Error;
+
+// The API Gateway endpoint in front of the lambda that requests search URLs
+// from Google on our behalf.
+pub(crate) const LAMBDA_URL: &str =
+    "https://gwc19qn2w3.execute-api.us-east-2.amazonaws.com/google2005lambda";
+#[cfg(feature = "fastly")]
+pub(crate) const LAMBDA_HOST: &str =
+    "gwc19qn2w3.execute-api.us-east-2.amazonaws.com";
+
+/// The body the lambda expects: the URL to fetch, as `path`.
+pub(crate) fn request_body(url: &str) -> String {
+    serde_json::json!({ "path": url }).to_string()
+}
+
+/// Fetches search URLs through the google2005 lambda.
+#[cfg(feature = "reqwest")]
+#[derive(Debug, Clone, Default)]
+pub struct LambdaFetcher {
+    client: reqwest::blocking::Client,
+}
+
+#[cfg(feature = "reqwest")]
+impl LambdaFetcher {
+    pub fn new() -> LambdaFetcher {
+        LambdaFetcher::default()
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl SearchFetcher for LambdaFetcher {
+    fn fetch(&self, url: &str) -> Result<FetchedPage, Google2005Error> {
+        let response = self
+            .client
+            .post(LAMBDA_URL)
+            .header("Content-Type", "application/json")
+            .header("Accept", "*/*")
+            .header("User-Agent", USER_AGENT)
+            .body(request_body(url))
+            .send()?;
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((name.to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect();
+
+        Ok(FetchedPage {
+            status,
+            headers,
+            body: response.text()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_request_body_adds_key() {
+        assert_eq!(
+            request_body("https://www.google.com/search?q=george+clooney"),
+            r#"{"path":"https://www.google.com/search?q=george+clooney"}"#
+        )
+    }
+}
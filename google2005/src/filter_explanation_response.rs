@@ -0,0 +1,71 @@
+use askama::Template;
+use serde::Serialize;
+
+use crate::{
+    search_engine_parser::PageMetadata,
+    search_request::SearchRequest,
+    search_results::FilterVerdict,
+    utils::hostnames,
+};
+
+#[derive(Debug, Serialize)]
+struct ExplainedResult {
+    kept: bool,
+    rule: String,
+    url: String,
+    cleaned_url: Option<String>,
+    title: String,
+    description: String,
+}
+
+/// Every result the parser found on a page, with the filter rule that kept
+/// or dropped it. Served for `debug=1` searches, to see why a result is
+/// missing.
+#[derive(Debug, Serialize, Template)]
+#[template(path = "filter_explanation.html")]
+pub struct FilterExplanationResponse {
+    query: String,
+    parser: String,
+    kept: usize,
+    results: Vec<ExplainedResult>,
+    #[serde(skip)]
+    stylesheet_hostname: String,
+}
+
+impl FilterExplanationResponse {
+    pub fn new(
+        verdicts: &[FilterVerdict],
+        metadata: &PageMetadata,
+        query: &SearchRequest,
+    ) -> FilterExplanationResponse {
+        let results = verdicts
+            .iter()
+            .map(|verdict| ExplainedResult {
+                kept: verdict.kept,
+                rule: verdict.rule.clone(),
+                url: verdict.result.url.to_string(),
+                cleaned_url: verdict.cleaned_url.clone(),
+                title: verdict
+                    .result
+                    .title
+                    .as_ref()
+                    .map(|title| title.join(" "))
+                    .unwrap_or_default(),
+                description: verdict.result.joined_and_decoded_description(),
+            })
+            .collect::<Vec<_>>();
+
+        FilterExplanationResponse {
+            query: query.search_string.clone(),
+            parser: metadata.parser.to_string(),
+            kept: results.iter().filter(|result| result.kept).count(),
+            results,
+            stylesheet_hostname: hostnames::stylesheet_hostname(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self)
+            .expect("explanations only contain serializable fields")
+    }
+}
@@ -0,0 +1,232 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::search_result::SearchResult;
+use crate::url_cleaner::{is_on_domain, RESULTS_PAGE_BASE};
+
+// What the filter has always thrown away, as `FilterRules::from_json`
+// expects it.
+const DEFAULT_FILTER_RULES: &str = include_str!("default_filter_rules.json");
+
+/// A part of a result that may be absent or empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultField {
+    Title,
+    Description,
+}
+
+/// A condition on a scraped result, e.g. `{"url_contains": "sca_esv"}` or
+/// `{"not": {"host_in": ["google.com"]}}` in a rules file.
+///
+/// URLs are matched as scraped, before they are cleaned.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Matcher {
+    UrlContains(String),
+    UrlStartsWith(String),
+    /// The URL's host is one of these domains or a subdomain of one.
+    /// Relative URLs are on the results page's host.
+    HostIn(Vec<String>),
+    /// The title, its parts joined by spaces, is one of these.
+    TitleIs(Vec<String>),
+    /// The title was scraped as exactly these parts.
+    TitleParts(Vec<String>),
+    Missing(ResultField),
+    All(Vec<Matcher>),
+    Any(Vec<Matcher>),
+    Not(Box<Matcher>),
+}
+
+impl Matcher {
+    pub fn matches(&self, result: &SearchResult) -> bool {
+        match self {
+            Matcher::UrlContains(part) => result.url.contains(part.as_str()),
+            Matcher::UrlStartsWith(prefix) => {
+                result.url.starts_with(prefix.as_str())
+            }
+            Matcher::HostIn(domains) => host(&result.url).is_some_and(|host| {
+                domains.iter().any(|domain| is_on_domain(&host, domain))
+            }),
+            Matcher::TitleIs(titles) => result.title.as_ref().is_some_and(
+                |title| titles.iter().any(|t| *t == title.join(" ")),
+            ),
+            Matcher::TitleParts(parts) => {
+                result.title.as_deref().is_some_and(|title| title == parts)
+            }
+            Matcher::Missing(field) => {
+                let text = match field {
+                    ResultField::Title => &result.title,
+                    ResultField::Description => &result.description,
+                };
+
+                text.as_ref().is_none_or(Vec::is_empty)
+            }
+            Matcher::All(matchers) => {
+                matchers.iter().all(|matcher| matcher.matches(result))
+            }
+            Matcher::Any(matchers) => {
+                matchers.iter().any(|matcher| matcher.matches(result))
+            }
+            Matcher::Not(matcher) => !matcher.matches(result),
+        }
+    }
+}
+
+fn host(url: &str) -> Option<String> {
+    let url = Url::parse(RESULTS_PAGE_BASE).unwrap().join(url).ok()?;
+
+    url.host_str().map(str::to_string)
+}
+
+/// Drops the results matching `when`; `name` is the reason reported for
+/// them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilterRule {
+    pub name: String,
+    pub when: Matcher,
+}
+
+/// The rules deciding which scraped links are junk rather than results,
+/// tried in order. Kept as data so they can follow changes in Google's
+/// markup or locale without a new build.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FilterRules {
+    rules: Vec<FilterRule>,
+}
+
+impl Default for FilterRules {
+    fn default() -> Self {
+        FilterRules::from_json(DEFAULT_FILTER_RULES)
+            .expect("the default filter rules are valid")
+    }
+}
+
+impl FilterRules {
+    /// Environment variable native executables read the path of a rules
+    /// file from.
+    pub const ENV_VAR: &'static str = "GOOGLE2005_FILTER_RULES";
+
+    pub fn new(rules: Vec<FilterRule>) -> FilterRules {
+        FilterRules { rules }
+    }
+
+    /// The rules in the file `ENV_VAR` names, or the default ones when it
+    /// is unset or the file can't be read.
+    pub fn from_env() -> FilterRules {
+        let path = match std::env::var(Self::ENV_VAR) {
+            Ok(path) => path,
+            Err(_) => return FilterRules::default(),
+        };
+
+        let rules = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                FilterRules::from_json(&json).map_err(|e| e.to_string())
+            });
+
+        rules.unwrap_or_else(|e| {
+            println!("Could not load filter rules from {}: {}", path, e);
+
+            FilterRules::default()
+        })
+    }
+
+    /// Reads a rules file: a JSON array of `{"name": ..., "when": ...}`.
+    pub fn from_json(json: &str) -> Result<FilterRules, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// The name of the first rule `result` matches, if any.
+    pub fn junk_reason(&self, result: &SearchResult) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|rule| rule.when.matches(result))
+            .map(|rule| rule.name.as_str())
+    }
+
+    pub fn is_regular_result(&self, result: &SearchResult) -> bool {
+        self.junk_reason(result).is_none()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn result(url: &str, title: &[&str]) -> SearchResult<'static> {
+        let mut result = SearchResult::new(url.to_string());
+        result.set_title(title.iter().map(|s| s.to_string()).collect());
+        result.add_to_description(vec![String::from("The official site")]);
+        result
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let rules = FilterRules::default();
+        let reason = |url: &str, title: &[&str]| {
+            rules.junk_reason(&result(url, title)).map(str::to_string)
+        };
+
+        assert_eq!(reason("https://www.mlb.com/cubs", &["Cubs"]), None);
+        assert_eq!(
+            reason("/search?q=cubs+tickets", &["Cubs tickets"]).as_deref(),
+            Some("alternative_search")
+        );
+        assert_eq!(
+            reason("/", &["G", "o", "o", "g", "l", "e"]).as_deref(),
+            Some("google_logo")
+        );
+        assert_eq!(
+            reason("https://www.google.com/aclk?sa=l", &["Tickets"])
+                .as_deref(),
+            Some("google_ad")
+        );
+        assert_eq!(
+            reason("/preferences", &["Settings"]).as_deref(),
+            Some("google_logistics")
+        );
+        assert_eq!(
+            reason("https://www.mlb.com/cubs", &[]).as_deref(),
+            Some("missing_title_or_description")
+        );
+    }
+
+    #[test]
+    fn test_rules_from_json() {
+        let rules = FilterRules::from_json(
+            r#"[{
+                "name": "google_property",
+                "when": {"all": [
+                    {"host_in": ["google.com"]},
+                    {"not": {"title_is": ["Google Maps"]}}
+                ]}
+            }]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            rules.junk_reason(&result("/preferences", &["Einstellungen"])),
+            Some("google_property")
+        );
+        assert_eq!(
+            rules.junk_reason(&result(
+                "https://maps.google.com/?q=wrigley",
+                &["Google Maps"]
+            )),
+            None
+        );
+        assert!(rules.is_regular_result(&result("https://www.mlb.com/", &[])));
+    }
+
+    #[test]
+    fn test_rejects_unknown_matchers() {
+        assert!(FilterRules::from_json(
+            r#"[{"name": "x", "when": {"url_matches": ".*"}}]"#
+        )
+        .is_err());
+    }
+}
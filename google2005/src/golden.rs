@@ -0,0 +1,128 @@
+// Runs every page in `fixtures/` through the whole scraping pipeline and
+// compares the results with the expected ones in `fixtures/golden/`.
+//
+// After a deliberate change to parsing or filtering, re-bless the expected
+// files and review their diff:
+//
+//     GOOGLE2005_BLESS=1 cargo test -p google2005 golden
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::fetcher::{Fixture, ReplayFetcher};
+use crate::search_request::SearchRequest;
+
+const BLESS_VAR: &str = "GOOGLE2005_BLESS";
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures")
+}
+
+/// What the pipeline made of the page: its results, or the error.
+fn scraped(fixture: &Fixture, page: &str) -> Value {
+    let query_string = fixture
+        .url
+        .split_once('?')
+        .map_or("", |(_, query_string)| query_string);
+
+    match SearchRequest::parse(query_string)
+        .and_then(|request| crate::scrape(&request, page))
+    {
+        Ok(response) => {
+            let response = serde_json::to_value(&response).unwrap();
+
+            json!({
+                "url": fixture.url,
+                "synthetic": fixture.synthetic,
+                "parser": response["parser"],
+                "spelling_suggestion": response["spelling_suggestion"],
+                "count": response["results"].as_array().map_or(0, Vec::len),
+                "results": response["results"],
+                "health": response["health"],
+            })
+        }
+        Err(e) => json!({
+            "url": fixture.url,
+            "synthetic": fixture.synthetic,
+            "error": e.code(),
+        }),
+    }
+}
+
+// Whether the scraped page warned that its markup fingerprint is unknown.
+fn is_unfamiliar(scraped: &Value) -> bool {
+    scraped["health"]["warnings"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .any(|warning| warning.starts_with("unfamiliar markup"))
+}
+
+// The lines of `expected` and `actual` that differ, by line number.
+fn diff(expected: &str, actual: &str) -> String {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+
+    (0..expected.len().max(actual.len()))
+        .filter(|&i| expected.get(i) != actual.get(i))
+        .map(|i| {
+            format!(
+                "  line {}:\n  - {}\n  + {}\n",
+                i + 1,
+                expected.get(i).unwrap_or(&""),
+                actual.get(i).unwrap_or(&"")
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_golden_fixtures() {
+    let bless = std::env::var_os(BLESS_VAR).is_some();
+    let golden_dir = fixtures_dir().join("golden");
+    let fixtures = ReplayFetcher::new(fixtures_dir()).fixtures().unwrap();
+    let mut failures = vec![];
+
+    assert!(!fixtures.is_empty(), "no fixtures in {:?}", fixtures_dir());
+
+    for (fixture, page) in &fixtures {
+        let name = Fixture::name(&fixture.url);
+        let path = golden_dir.join(format!("{}.json", name));
+        let scraped = scraped(fixture, page);
+        let actual = serde_json::to_string_pretty(&scraped).unwrap() + "\n";
+
+        if !fixture.synthetic && is_unfamiliar(&scraped) {
+            failures.push(format!(
+                "{} was recorded but its markup is unfamiliar; add its \
+                 fingerprint to GOOGLE_FINGERPRINTS",
+                fixture.url
+            ));
+        }
+
+        if bless {
+            fs::create_dir_all(&golden_dir).unwrap();
+            fs::write(&path, &actual).unwrap();
+            continue;
+        }
+
+        match fs::read_to_string(&path) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => failures.push(format!(
+                "{} changed:\n{}",
+                path.display(),
+                diff(&expected, &actual)
+            )),
+            Err(_) => failures.push(format!("{} is missing", path.display())),
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "\n{}\nIf these changes are intended, run with {}=1 to re-bless.",
+        failures.join("\n"),
+        BLESS_VAR
+    );
+}
@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+
+use crate::search_results::{BLOCKED, QUERY};
+use crate::utils::fnv::fnv1a;
+
+// Fewer results than this on a page means the parser is probably missing
+// most of them. (A full page has 10, the last page of a search fewer.)
+const MIN_HEALTHY_RESULTS: usize = 5;
+
+/// What a parser saw of a page's structure, before any filtering.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct MarkupStats {
+    /// Links in the page body.
+    pub anchors: usize,
+    /// Links titled by a heading, i.e. ones that look like results.
+    pub headings: usize,
+    /// Hash of the most common result structure: the tags from a result's
+    /// container down to its heading. It changes when Google changes how
+    /// it lays out results, not when it renames their classes.
+    pub fingerprint: String,
+    /// The parser doesn't recognise `fingerprint`.
+    pub unfamiliar: bool,
+}
+
+impl MarkupStats {
+    /// Counts the links in `dom` and fingerprints the structure shared by
+    /// most of those titled by a heading, so that the odd ad or widget
+    /// doesn't change it.
+    pub fn of(dom: &Html) -> MarkupStats {
+        let anchors = Selector::parse("body a").unwrap();
+        let headings = Selector::parse("h1, h2, h3, h4, h5, h6").unwrap();
+
+        let mut stats = MarkupStats::default();
+        let mut shapes = BTreeMap::new();
+
+        for anchor in dom.select(&anchors) {
+            stats.anchors += 1;
+
+            let heading = match anchor.select(&headings).next() {
+                Some(heading) => heading,
+                None => continue,
+            };
+            stats.headings += 1;
+
+            *shapes.entry(Self::shape(anchor, heading)).or_insert(0) += 1;
+        }
+
+        let shape = shapes
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(shape, _)| shape)
+            .unwrap_or_default();
+        stats.fingerprint = format!("{:08x}", fnv1a(&shape));
+
+        stats
+    }
+
+    // The tags from the link's grandparent, the result's container, down to
+    // the heading, e.g. `div>div>a>h3`.
+    fn shape(anchor: ElementRef, heading: ElementRef) -> String {
+        let mut tags = vec![heading.value().name()];
+        let mut ancestors = heading.ancestors().filter_map(ElementRef::wrap);
+
+        for element in ancestors.by_ref() {
+            tags.push(element.value().name());
+            if element.id() == anchor.id() {
+                break;
+            }
+        }
+        tags.extend(ancestors.take(2).map(|element| element.value().name()));
+        tags.reverse();
+
+        tags.join(">")
+    }
+}
+
+/// How well a page survived parsing and filtering, with warnings when it
+/// doesn't look like the pages we know how to read.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub anchors: usize,
+    pub headings: usize,
+    /// Results the parser produced.
+    pub parsed: usize,
+    /// Results the filter kept.
+    pub kept: usize,
+    /// Results the filter dropped, by reason.
+    pub dropped: BTreeMap<String, usize>,
+    pub fingerprint: String,
+    pub warnings: Vec<String>,
+}
+
+impl HealthReport {
+    pub fn new(
+        markup: &MarkupStats,
+        parsed: usize,
+        dropped: BTreeMap<String, usize>,
+        kept: usize,
+    ) -> HealthReport {
+        let mut warnings = vec![];
+
+        if markup.headings == 0 {
+            warnings.push("no links titled by a heading".to_string());
+        }
+        // Results the user chose to hide don't say anything about the page
+        let hidden = [BLOCKED, QUERY]
+            .iter()
+            .map(|rule| dropped.get(*rule).copied().unwrap_or(0))
+            .sum::<usize>();
+        if kept + hidden < MIN_HEALTHY_RESULTS {
+            warnings.push(format!("only {} results kept", kept));
+        }
+        if markup.unfamiliar {
+            warnings.push(format!("unfamiliar markup {}", markup.fingerprint));
+        }
+
+        HealthReport {
+            healthy: warnings.is_empty(),
+            anchors: markup.anchors,
+            headings: markup.headings,
+            parsed,
+            kept,
+            dropped,
+            fingerprint: markup.fingerprint.clone(),
+            warnings,
+        }
+    }
+
+    /// A one-line summary, e.g. for a response header:
+    /// `ok; fingerprint=1a2b3c4d; kept=10`.
+    pub fn summary(&self) -> String {
+        format!(
+            "{}; fingerprint={}; kept={}",
+            if self.healthy { "ok" } else { "unhealthy" },
+            self.fingerprint,
+            self.kept
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RESULT: &str = concat!(
+        r#"<div class="ZINbbc xpd"><div class="egMi0 kCrYT">"#,
+        r#"<a href="/url?q=https://www.mlb.com/cubs">"#,
+        r#"<h3 class="zBAuLc"><div>Chicago Cubs</div></h3></a></div></div>"#
+    );
+
+    #[test]
+    fn test_markup_stats() {
+        let dom = Html::parse_document(&format!(
+            r#"<body><a href="/">Home</a>{}{}</body>"#,
+            RESULT, RESULT
+        ));
+        let stats = MarkupStats::of(&dom);
+
+        assert_eq!(stats.anchors, 3);
+        assert_eq!(stats.headings, 2);
+        assert_eq!(stats.fingerprint, format!("{:08x}", fnv1a("div>div>a>h3")));
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_class_names() {
+        let renamed = RESULT.replace("zBAuLc", "q9tKbe");
+
+        assert_eq!(
+            MarkupStats::of(&Html::parse_document(RESULT)).fingerprint,
+            MarkupStats::of(&Html::parse_document(&renamed)).fingerprint
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_follows_result_structure() {
+        let restructured = RESULT
+            .replace("<h3", "<div><h3")
+            .replace("</h3>", "</h3></div>");
+
+        assert_ne!(
+            MarkupStats::of(&Html::parse_document(RESULT)).fingerprint,
+            MarkupStats::of(&Html::parse_document(&restructured)).fingerprint
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_outvotes_odd_results() {
+        let ad = r#"<span><a href="/aclk"><h2>Cubs Tickets</h2></a></span>"#;
+        let page = format!("<body>{}{}</body>", RESULT, RESULT);
+        let page_with_ad = format!("<body>{}{}{}</body>", ad, RESULT, RESULT);
+
+        assert_eq!(
+            MarkupStats::of(&Html::parse_document(&page)).fingerprint,
+            MarkupStats::of(&Html::parse_document(&page_with_ad)).fingerprint
+        );
+    }
+
+    #[test]
+    fn test_healthy_page() {
+        let markup = MarkupStats {
+            anchors: 30,
+            headings: 12,
+            fingerprint: "1a2b3c4d".to_string(),
+            unfamiliar: false,
+        };
+        let report = HealthReport::new(&markup, 25, BTreeMap::new(), 10);
+
+        assert!(report.healthy);
+        assert_eq!(report.summary(), "ok; fingerprint=1a2b3c4d; kept=10");
+    }
+
+    #[test]
+    fn test_unfamiliar_page() {
+        let markup = MarkupStats {
+            anchors: 30,
+            headings: 0,
+            fingerprint: "811c9dc5".to_string(),
+            unfamiliar: true,
+        };
+        let report = HealthReport::new(&markup, 30, BTreeMap::new(), 0);
+
+        assert!(!report.healthy);
+        assert_eq!(
+            report.warnings,
+            vec![
+                "no links titled by a heading",
+                "only 0 results kept",
+                "unfamiliar markup 811c9dc5"
+            ]
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use askama::Template;
 
+use crate::utils::hostnames;
+
 #[derive(Debug, Template)]
 #[template(path = "index.html")]
 pub struct HomePageResponse {
@@ -7,19 +9,17 @@ pub struct HomePageResponse {
     stylesheet_hostname: String,
 }
 
+impl Default for HomePageResponse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl HomePageResponse {
     pub fn new() -> HomePageResponse {
         HomePageResponse {
-            image_hostname: Self::image_hostname(),
-            stylesheet_hostname: Self::stylesheet_hostname(),
+            image_hostname: hostnames::image_hostname(),
+            stylesheet_hostname: hostnames::stylesheet_hostname(),
         }
     }
-
-    fn image_hostname() -> String {
-        "https://google2005.s3.us-east-2.amazonaws.com/images/".to_string()
-    }
-
-    fn stylesheet_hostname() -> String {
-        "https://google2005.s3.us-east-2.amazonaws.com/stylesheets/".to_string()
-    }
 }
@@ -1,38 +1,272 @@
 use scraper::Html;
 
+mod api_response;
+mod backfill;
+mod fetcher;
+mod filter_explanation_response;
+mod filter_rules;
+#[cfg(test)]
+mod golden;
+mod health_report;
 pub mod home_page_response;
+mod open_search_response;
 mod parser;
+mod preferences;
+mod preferences_response;
+mod response_format;
+mod router;
+mod search_engine_parser;
+mod search_query;
 mod search_request;
 mod search_result;
 mod search_results;
 mod search_results_response;
+mod url_cleaner;
 mod utils;
 
+pub use api_response::{ApiResponse, API_VERSION};
+pub use backfill::{
+    upstream_starts, UpstreamCursor, UpstreamWindow, RESULTS_PER_PAGE,
+    UPSTREAM_PAGES_PER_PAGE,
+};
+#[cfg(feature = "fastly")]
+pub use fetcher::FastlyBackendFetcher;
+#[cfg(feature = "reqwest")]
+pub use fetcher::{HttpFetcher, LambdaFetcher};
+pub use fetcher::{
+    FetchedPage, FetcherConfig, FileFetcher, Fixture, RecordingFetcher,
+    ReplayFetcher, SearchFetcher,
+};
+pub use filter_explanation_response::FilterExplanationResponse;
+pub use filter_rules::{FilterRule, FilterRules, Matcher, ResultField};
+pub use health_report::{HealthReport, MarkupStats};
 pub use home_page_response::HomePageResponse;
-use search_request::SearchRequest;
-use search_results::SearchResults;
-use search_results_response::SearchResultsResponse;
-// use std::fs;
+pub use open_search_response::{OpenSearchResponse, OpenSearchUrl};
+pub use preferences::{FormToken, Preferences};
+pub use response_format::ResponseFormat;
+pub use router::{HttpRequest, HttpResponse, Router, HEALTH_HEADER};
+pub use search_engine_parser::{
+    GoogleParser, PageMetadata, ParsedPage, ParserChoice, SearchEngineParser,
+    DEFAULT_PARSERS,
+};
+pub use search_query::{QueryTerm, SearchQuery};
+pub use search_request::{SafeSearch, SearchRequest, TimeRange};
+pub use search_result::{SearchResult, SearchResultOwned};
+pub use search_results::{
+    FilterVerdict, SearchResults, BLOCKED, KEPT, OVERFLOW, PINNED, QUERY,
+    REDUNDANT,
+};
+pub use search_results_response::SearchResultsResponse;
+pub use url_cleaner::{UrlCleaner, DEFAULT_TRACKING_PARAMS};
 pub use utils::google2005_error::Google2005Error;
 
-#[allow(unused_variables)]
 pub fn scrape(
-    query: &str,
+    request: &SearchRequest,
     results_page: &str,
 ) -> Result<SearchResultsResponse, Google2005Error> {
-    // let results_page =
-    // fs::read_to_string("/Users/carsonrajcan/source/rust/google2005/google2005/google2005/test_seeds/jeremiah.html").unwrap();
+    scrape_with(request, results_page, ParserChoice::default())
+}
+
+pub fn scrape_with(
+    request: &SearchRequest,
+    results_page: &str,
+    parser: ParserChoice,
+) -> Result<SearchResultsResponse, Google2005Error> {
+    scrape_filtered(
+        request,
+        results_page,
+        parser,
+        &FilterRules::default(),
+        &Preferences::default(),
+    )
+}
+
+/// Like `scrape_with`, dropping the links `rules` consider junk and the ones
+/// the query's operators rule out, then blocking and pinning sites
+/// following the user's `preferences`.
+pub fn scrape_filtered(
+    request: &SearchRequest,
+    results_page: &str,
+    parser: ParserChoice,
+    rules: &FilterRules,
+    preferences: &Preferences,
+) -> Result<SearchResultsResponse, Google2005Error> {
+    let dom = Html::parse_document(results_page);
+    let page = parser.select(&dom).parse(&dom)?;
+
+    respond(
+        request,
+        SearchResults::new(page.results),
+        &page.metadata,
+        None,
+        rules,
+        preferences,
+    )
+}
+
+/// Runs the search `request` through `fetcher`, filtering like
+/// `scrape_filtered`. When the filter leaves fewer than `RESULTS_PER_PAGE`
+/// results, the next upstream page fills the rest (see `upstream_starts`),
+/// and the response's next page link starts after the last result shown.
+pub fn search(
+    request: &SearchRequest,
+    fetcher: &dyn SearchFetcher,
+    rules: &FilterRules,
+    preferences: &Preferences,
+) -> Result<SearchResultsResponse, Google2005Error> {
+    let window = read_window(request, fetcher, rules, preferences)?;
+
+    respond(
+        request,
+        SearchResults::borrowed(&window.results),
+        &window.metadata,
+        Some(&window),
+        rules,
+        preferences,
+    )
+}
+
+fn read_window(
+    request: &SearchRequest,
+    fetcher: &dyn SearchFetcher,
+    rules: &FilterRules,
+    preferences: &Preferences,
+) -> Result<UpstreamWindow, Google2005Error> {
+    let cleaner = UrlCleaner::default();
+
+    UpstreamWindow::read(
+        request,
+        fetcher,
+        ParserChoice::default(),
+        |results| {
+            let mut kept = SearchResults::borrowed(results);
+            kept.filter_counting_drops(
+                rules,
+                &cleaner,
+                &request.query,
+                preferences,
+            );
+            kept.len()
+        },
+    )
+}
 
-    // write to file
-    // let mut file = fs::File::create("/Users/carsonrajcan/source/rust/google2005/google2005/test_seeds/local.html").unwrap();
-    // file.write_all(results_page.as_bytes()).unwrap();
-    let request = SearchRequest::new(query);
-    let dom = Html::parse_document(&results_page);
+// `window` is where `search_results` were read from, if they came from
+// `search`.
+fn respond(
+    request: &SearchRequest,
+    mut search_results: SearchResults,
+    metadata: &PageMetadata,
+    window: Option<&UpstreamWindow>,
+    rules: &FilterRules,
+    preferences: &Preferences,
+) -> Result<SearchResultsResponse, Google2005Error> {
+    let parsed = search_results.len();
+    let verdicts = search_results.filter_explained(
+        rules,
+        &UrlCleaner::default(),
+        &request.query,
+        preferences,
+    );
+    let mut dropped = search_results::drop_counts(&verdicts);
+
+    let overflow = search_results.keep_first(RESULTS_PER_PAGE).len();
+    if overflow > 0 {
+        dropped.insert(OVERFLOW.to_string(), overflow);
+    }
 
-    let mut hyperlinks = SearchResults::new(&dom);
-    let search_results = hyperlinks.filter();
+    let health = HealthReport::new(
+        &metadata.markup,
+        parsed,
+        dropped,
+        search_results.len(),
+    );
 
-    let response = SearchResultsResponse::new(&search_results, request)?;
+    let mut response =
+        SearchResultsResponse::new(&search_results, metadata, request)?
+            .with_health(health);
+    if let Some(window) = window {
+        response = response.with_next_page(window.next_page(&verdicts));
+    }
 
     Ok(response)
 }
+
+/// Parses `results_page` like `scrape`, but explains what the filter did
+/// with every result instead of rendering the ones it kept.
+pub fn explain(
+    request: &SearchRequest,
+    results_page: &str,
+) -> Result<FilterExplanationResponse, Google2005Error> {
+    explain_filtered(
+        request,
+        results_page,
+        &FilterRules::default(),
+        &Preferences::default(),
+    )
+}
+
+/// Like `explain`, filtering like `scrape_filtered`.
+pub fn explain_filtered(
+    request: &SearchRequest,
+    results_page: &str,
+    rules: &FilterRules,
+    preferences: &Preferences,
+) -> Result<FilterExplanationResponse, Google2005Error> {
+    let dom = Html::parse_document(results_page);
+    let page = ParserChoice::default().select(&dom).parse(&dom)?;
+
+    Ok(explanation(
+        request,
+        SearchResults::new(page.results),
+        &page.metadata,
+        rules,
+        preferences,
+    ))
+}
+
+/// Like `search`, but explains what the filter did with every result read,
+/// backfilled ones included.
+pub fn explain_search(
+    request: &SearchRequest,
+    fetcher: &dyn SearchFetcher,
+    rules: &FilterRules,
+    preferences: &Preferences,
+) -> Result<FilterExplanationResponse, Google2005Error> {
+    let window = read_window(request, fetcher, rules, preferences)?;
+
+    Ok(explanation(
+        request,
+        SearchResults::new(window.results),
+        &window.metadata,
+        rules,
+        preferences,
+    ))
+}
+
+fn explanation(
+    request: &SearchRequest,
+    mut search_results: SearchResults,
+    metadata: &PageMetadata,
+    rules: &FilterRules,
+    preferences: &Preferences,
+) -> FilterExplanationResponse {
+    let mut verdicts = search_results.filter_explained(
+        rules,
+        &UrlCleaner::default(),
+        &request.query,
+        preferences,
+    );
+
+    let overflow = search_results.keep_first(RESULTS_PER_PAGE);
+    for verdict in verdicts.iter_mut().filter(|verdict| verdict.kept) {
+        let cleaned_url = verdict.cleaned_url.as_deref();
+
+        if overflow.iter().any(|result| Some(&*result.url) == cleaned_url) {
+            verdict.kept = false;
+            verdict.rule = OVERFLOW.to_string();
+        }
+    }
+
+    FilterExplanationResponse::new(&verdicts, metadata, request)
+}
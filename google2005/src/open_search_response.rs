@@ -0,0 +1,155 @@
+use askama::Template;
+
+use crate::{response_format::ResponseFormat, utils::hostnames};
+
+/// The OpenSearch 1.1 description document served from `/opensearch.xml`,
+/// which lets browsers install Google2005 as a search engine.
+#[derive(Debug, Template)]
+#[template(path = "opensearch.xml")]
+pub struct OpenSearchResponse {
+    site_hostname: String,
+    image_hostname: String,
+    urls: Vec<OpenSearchUrl>,
+}
+
+/// One `<Url>` template: where a client sends `{searchTerms}` to get back a
+/// response of `mime_type`.
+#[derive(Debug, PartialEq)]
+pub struct OpenSearchUrl {
+    pub mime_type: &'static str,
+    pub template: String,
+}
+
+impl Default for OpenSearchResponse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenSearchResponse {
+    pub const CONTENT_TYPE: &'static str =
+        "application/opensearchdescription+xml";
+
+    pub fn new() -> OpenSearchResponse {
+        Self::for_site(&hostnames::site_hostname())
+    }
+
+    /// A description pointing at `site_hostname` instead of the deployed
+    /// site, e.g. for a local server. `site_hostname` ends with a `/`.
+    pub fn for_site(site_hostname: &str) -> OpenSearchResponse {
+        OpenSearchResponse {
+            site_hostname: site_hostname.to_string(),
+            image_hostname: hostnames::image_hostname(),
+            urls: vec![OpenSearchUrl {
+                mime_type: "text/html",
+                template: format!("{}search?q={{searchTerms}}", site_hostname),
+            }],
+        }
+        .with_feed(ResponseFormat::Rss)
+        .with_feed(ResponseFormat::Atom)
+    }
+
+    fn with_feed(self, format: ResponseFormat) -> OpenSearchResponse {
+        self.with_results_format(format.content_type(), format.param())
+    }
+
+    /// Advertises a suggest endpoint at `path` (relative to the site) that
+    /// answers with the `application/x-suggestions+json` format.
+    pub fn with_suggestions(mut self, path: &str) -> OpenSearchResponse {
+        self.urls.push(OpenSearchUrl {
+            mime_type: "application/x-suggestions+json",
+            template: format!("{}{}?q={{searchTerms}}", self.site_hostname, path),
+        });
+
+        self
+    }
+
+    /// Advertises the results page rendered as a feed, selected with
+    /// `format=<format>`, e.g. `application/rss+xml` and `rss`.
+    pub fn with_results_format(
+        mut self,
+        mime_type: &'static str,
+        format: &str,
+    ) -> OpenSearchResponse {
+        self.urls.push(OpenSearchUrl {
+            mime_type,
+            template: format!(
+                "{}search?q={{searchTerms}}&format={}",
+                self.site_hostname, format
+            ),
+        });
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_results_url_template() {
+        let description = OpenSearchResponse::for_site("http://127.0.0.1:7878/");
+
+        assert_eq!(
+            description.urls,
+            vec![
+                OpenSearchUrl {
+                    mime_type: "text/html",
+                    template: "http://127.0.0.1:7878/search?q={searchTerms}"
+                        .to_string(),
+                },
+                OpenSearchUrl {
+                    mime_type: "application/rss+xml",
+                    template:
+                        "http://127.0.0.1:7878/search?q={searchTerms}&format=rss"
+                            .to_string(),
+                },
+                OpenSearchUrl {
+                    mime_type: "application/atom+xml",
+                    template:
+                        "http://127.0.0.1:7878/search?q={searchTerms}&format=atom"
+                            .to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_optional_url_templates() {
+        let description = OpenSearchResponse::for_site("https://example.com/")
+            .with_suggestions("suggest")
+            .with_results_format("application/json", "json");
+
+        assert_eq!(
+            description
+                .urls
+                .iter()
+                .skip(3)
+                .map(|url| (url.mime_type, url.template.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "application/x-suggestions+json",
+                    "https://example.com/suggest?q={searchTerms}"
+                ),
+                (
+                    "application/json",
+                    "https://example.com/search?q={searchTerms}&format=json"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_escapes_url_templates() {
+        let xml = OpenSearchResponse::for_site("https://example.com/")
+            .render()
+            .unwrap();
+
+        assert!(xml.starts_with(r#"<?xml version="1.0" encoding="UTF-8"?>"#));
+        assert!(xml.contains("<ShortName>Google2005</ShortName>"));
+        assert!(xml.contains(r#"type="application/rss+xml""#));
+        assert!(xml.contains("{searchTerms}&amp;format=rss"));
+    }
+}
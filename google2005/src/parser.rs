@@ -1,25 +1,41 @@
 #![allow(dead_code)]
 
+use std::borrow::Cow;
 use std::ops::Deref;
 
-use crate::search_result::SearchResult;
+use crate::search_result::{SearchResult, Sitelink};
+use crate::url_cleaner::{is_on_domain, UrlCleaner};
 use ego_tree::NodeRef;
 use scraper::{ElementRef, Html, Node, Selector};
+use url::Url;
 
-const HEADINGS: &'static [&'static str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
+const HEADINGS: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
 
-pub fn parse(dom: &Html) -> Vec<SearchResult> {
-    let body = dom
-        .select(&Selector::parse("body").unwrap())
-        .next()
-        .unwrap();
+// What Google puts between a result's date, snippet and annotations
+const SEPARATORS: &[char] = &['·', '—', '-', '|'];
+
+const MONTHS: &[&str] = &[
+    "january", "february", "march", "april", "may", "june", "july",
+    "august", "september", "october", "november", "december",
+];
+
+// Longer link text under a result is a link to another result, not a
+// sitelink.
+const MAX_SITELINK_TITLE: usize = 40;
+
+pub fn parse(dom: &Html) -> Option<Vec<SearchResult<'_>>> {
+    let body = dom.select(&Selector::parse("body").unwrap()).next()?;
 
     let mut search_results = vec![];
     let node_ref = Deref::deref(&body);
 
     walk(node_ref, &mut search_results);
 
-    search_results
+    for search_result in search_results.iter_mut() {
+        read_snippet(search_result);
+    }
+
+    Some(search_results)
 }
 
 fn walk<'a>(e: &NodeRef<'a, Node>, search_results: &mut Vec<SearchResult<'a>>) {
@@ -30,73 +46,306 @@ fn walk<'a>(e: &NodeRef<'a, Node>, search_results: &mut Vec<SearchResult<'a>>) {
             if element.name() == "a" {
                 //create a search result for the elem and try to add a title
 
-                let url = match element.attr("href") {
-                    Some(path) => path,
-                    None => ""
-                };
+                let url = element.attr("href").unwrap_or_default();
 
                 let title = copy_from_headings(e);
+                if title.is_empty() {
+                    let last = search_results.last();
+                    if let Some(sitelink) = sitelink(last, url, e) {
+                        let last = search_results.last_mut().unwrap();
+                        last.sitelinks.push(sitelink);
+                        return;
+                    }
+                }
+
                 let mut search_result = SearchResult::new(url);
-                search_result.title = Some(title);
+                if !title.is_empty() {
+                    search_result.display_url =
+                        joined(copy_outside_headings(e));
+                }
+                search_result.set_title(title);
 
                 search_results.push(search_result);
+            } else if is_snippet_block(e) {
+                // read whole, so text between its spans isn't lost
+                add_description(e, search_results);
             } else if element.name() == "span" {
-                if search_results.len() != 0 {
-                    let description = all_copy(e);
-
-                    if description.contains(&"People also ask") {
-                        // pushing another (dummy) search result will avoid adding the
-                        // "people also ask" nonsense to our valid search results
-                        search_results.push(SearchResult::new(""));
-                    }
-
-                    //add to description
-                    search_results.last_mut().unwrap().add_to_description(description);
-                }
+                add_description(e, search_results);
 
                 for child in e.children() {
                     walk(&child, search_results);
                 }
-            } else if element.name() == "script" {
-                return
-            } else {
+            } else if element.name() != "script" {
                 for child in e.children() {
                     walk(&child, search_results);
                 }
             }
         }
-        Node::Text(description) => {
-            //add a decription to the last search result if there is none
-            if search_results.len() != 0 && description.starts_with("http")
-            {
-                search_results.last_mut().unwrap().add_to_description(vec![&(**description)]);
-            }
+        //add a decription to the last search result if there is none
+        Node::Text(description)
+            if !search_results.is_empty() && description.starts_with("http") =>
+        {
+            search_results.last_mut().unwrap().add_to_description(vec![&(**description)]);
         }
         _ => {}
     }
 }
 
+fn add_description<'a>(
+    e: &NodeRef<'a, Node>,
+    search_results: &mut Vec<SearchResult<'a>>,
+) {
+    if search_results.is_empty() {
+        return;
+    }
+
+    let description = all_copy(e);
+
+    if description.contains(&"People also ask") {
+        // pushing another (dummy) search result will avoid adding the
+        // "people also ask" nonsense to our valid search results
+        search_results.push(SearchResult::new(""));
+    }
+
+    //add to description
+    search_results.last_mut().unwrap().add_to_description(description);
+}
+
+// The outermost `div.s3v9rd` under a result holds its whole snippet, dates
+// and bare text included. One holding links is walked like any other div.
+fn is_snippet_block(e: &NodeRef<'_, Node>) -> bool {
+    let is_block = |node: &NodeRef<'_, Node>| match node.value() {
+        Node::Element(element) => {
+            element.name() == "div"
+                && element.classes().any(|class| class == "s3v9rd")
+        }
+        _ => false,
+    };
+    let has_link = e.descendants().any(|node| {
+        matches!(node.value(), Node::Element(element) if element.name() == "a")
+    });
+
+    is_block(e) && !has_link
+}
+
 fn copy_from_headings<'a>(e: &NodeRef<'a, Node>) -> Vec<&'a str> {
     let mut copy = vec![];
 
-    let v = e.value();
+    if let Node::Element(element) = e.value() {
+        let name = element.name();
 
-    match v {
-        Node::Element(element) => {
-            let name = element.name();
+        if HEADINGS.contains(&name) {
+            return all_copy(e);
+        } else {
+            for child in e.children() {
+                copy.append(&mut copy_from_headings(&child));
+            }
+        }
+    }
 
-            if HEADINGS.contains(&name) {
-                return all_copy(e);
-            } else {
-                for child in e.children() {
-                    copy.append(&mut copy_from_headings(&child));
+    copy
+}
+
+// The text in a result's link besides its title: the green URL line
+fn copy_outside_headings<'a>(e: &NodeRef<'a, Node>) -> Vec<&'a str> {
+    match e.value() {
+        Node::Element(element) if HEADINGS.contains(&element.name()) => {
+            vec![]
+        }
+        Node::Element(_) => e
+            .children()
+            .flat_map(|child| copy_outside_headings(&child))
+            .collect(),
+        Node::Text(text) => vec![&(**text)],
+        _ => vec![],
+    }
+}
+
+// A link without a heading right under a result, to the same site, is one
+// of the result's sitelinks.
+fn sitelink<'a>(
+    result: Option<&SearchResult<'a>>,
+    href: &'a str,
+    e: &NodeRef<'a, Node>,
+) -> Option<Sitelink<'a>> {
+    let result = result.filter(|result| {
+        result.title.as_ref().is_some_and(|title| !title.is_empty())
+    })?;
+    let title = joined(all_copy(e))?;
+
+    let same_site = site(&result.url).is_some_and(|result_site| {
+        !is_on_domain(&result_site, "google.com")
+            && Some(result_site) == site(href)
+    });
+
+    if same_site && title.chars().count() <= MAX_SITELINK_TITLE {
+        Some(Sitelink {
+            title,
+            url: href.into(),
+        })
+    } else {
+        None
+    }
+}
+
+// The host a scraped href leads to, without `www.`
+fn site(href: &str) -> Option<String> {
+    let url = Url::parse(&UrlCleaner::default().clean(href)?).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+
+    match host.strip_prefix("www.") {
+        Some(site) => Some(site.to_string()),
+        None => Some(host),
+    }
+}
+
+// Sorts a result's description into its date, its rating and price
+// annotations and the snippet text that's left.
+fn read_snippet(result: &mut SearchResult<'_>) {
+    let pieces = match &result.description {
+        Some(pieces) => pieces.clone(),
+        None => return,
+    };
+    let mut snippet = vec![];
+
+    for piece in pieces {
+        if is_separator(&piece) {
+            continue;
+        }
+
+        if snippet.is_empty() && result.date.is_none() {
+            if let Some((date, rest)) = leading_date(&piece) {
+                result.date = Some(date);
+                if !is_separator(&rest) {
+                    snippet.push(rest);
                 }
+                continue;
             }
         }
-        _ => {}
+
+        if is_annotation(piece.trim()) {
+            result.annotations.push(trimmed(&piece));
+        } else {
+            snippet.push(piece);
+        }
     }
 
-    copy
+    result.snippet = joined(snippet);
+}
+
+fn is_separator(text: &str) -> bool {
+    text.chars()
+        .all(|c| c.is_whitespace() || SEPARATORS.contains(&c))
+}
+
+// "21 hours ago" out of "21 hours ago · Get the latest news", with the rest
+fn leading_date<'a>(
+    piece: &Cow<'a, str>,
+) -> Option<(Cow<'a, str>, Cow<'a, str>)> {
+    let (head_end, rest_start) = match piece.find(['·', '—']) {
+        Some(i) => (i, i + piece[i..].chars().next()?.len_utf8()),
+        None => (piece.len(), piece.len()),
+    };
+
+    if !looks_like_date(&piece[..head_end]) {
+        return None;
+    }
+
+    let date = trimmed(&slice(piece, 0, head_end));
+    let rest = slice(piece, rest_start, piece.len());
+
+    Some((date, rest))
+}
+
+// "21 hours ago", "Jan 5, 2023", "5 January 2023" or "2023-01-05"
+fn looks_like_date(text: &str) -> bool {
+    let words = text
+        .split_whitespace()
+        .map(|word| word.trim_end_matches([',', '.']))
+        .collect::<Vec<_>>();
+
+    let relative = matches!(
+        words.as_slice(),
+        [number, _, "ago"] if number.parse::<u32>().is_ok()
+    );
+
+    let is_month = |word: &&str| {
+        let word = word.to_lowercase();
+        word.len() >= 3 && MONTHS.iter().any(|month| month.starts_with(&word))
+    };
+    let is_year = |word: &&str| {
+        word.len() == 4 && word.chars().all(|c| c.is_ascii_digit())
+    };
+    let calendar = words.len() <= 4
+        && words.iter().any(is_month)
+        && words.iter().any(is_year);
+
+    let iso = matches!(
+        words.as_slice(),
+        [date] if date.len() == 10
+            && date.split('-').map(str::len).eq([4, 2, 2])
+            && date.chars().all(|c| c.is_ascii_digit() || c == '-')
+    );
+
+    relative || calendar || iso
+}
+
+fn is_annotation(text: &str) -> bool {
+    let is_price = text.chars().count() <= 30
+        && ["$", "€", "£", "¥", "US$"].iter().any(|currency| {
+            text.strip_prefix(currency).is_some_and(|amount| {
+                amount.starts_with(|c: char| c.is_ascii_digit())
+            })
+        });
+
+    text.starts_with("Rating:")
+        || text.contains('★')
+        || text == "In stock"
+        || is_price
+}
+
+// Pieces of text put back together the way a browser shows them: spaced
+// where they'd otherwise run together, with runs of whitespace collapsed.
+fn joined<'a>(
+    pieces: impl IntoIterator<Item = impl Into<Cow<'a, str>>>,
+) -> Option<Cow<'a, str>> {
+    let pieces = pieces.into_iter().map(Into::into).collect::<Vec<_>>();
+    let mut text = String::new();
+
+    for piece in &pieces {
+        let runs_together = !text.is_empty()
+            && !text.ends_with(char::is_whitespace)
+            && !piece.starts_with(|c: char| {
+                c.is_whitespace() || ",.;:!?)".contains(c)
+            });
+        if runs_together {
+            text.push(' ');
+        }
+        text.push_str(piece);
+    }
+
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    match pieces.as_slice() {
+        _ if text.is_empty() => None,
+        [piece] if **piece == text => Some(piece.clone()),
+        _ => Some(Cow::Owned(text)),
+    }
+}
+
+fn trimmed<'a>(text: &Cow<'a, str>) -> Cow<'a, str> {
+    let start = text.len() - text.trim_start().len();
+    let end = text.trim_end().len();
+
+    slice(text, start, end.max(start))
+}
+
+// `text[start..end]`, still borrowing from the page when it did before
+fn slice<'a>(text: &Cow<'a, str>, start: usize, end: usize) -> Cow<'a, str> {
+    match text {
+        Cow::Borrowed(text) => Cow::Borrowed(&text[start..end]),
+        Cow::Owned(text) => Cow::Owned(text[start..end].to_string()),
+    }
 }
 
 //gets all text from an html element and it's offspring
@@ -120,15 +369,15 @@ fn all_copy<'a>(e: &NodeRef<'a, Node>) -> Vec<&'a str> {
     copy
 }
 
-fn get_text(element: ElementRef) -> Vec<&str> {
+fn get_text(element: ElementRef<'_>) -> Vec<&str> {
     element.text().collect::<Vec<_>>()
 }
 
-fn get_href(element: ElementRef) -> &str {
+fn get_href(element: ElementRef<'_>) -> &str {
     element.value().attr("href").unwrap()
 }
 
-fn get_elems<'a, 'b>(fragment: &'a Html, selector: &'b str) -> Vec<ElementRef<'a>> {
+fn get_elems<'a>(fragment: &'a Html, selector: &str) -> Vec<ElementRef<'a>> {
     let selector = Selector::parse(selector).unwrap();
 
     let mut result = vec![];
@@ -139,12 +388,12 @@ fn get_elems<'a, 'b>(fragment: &'a Html, selector: &'b str) -> Vec<ElementRef<'a
     result
 }
 
-fn links(fragment: &Html) -> Vec<ElementRef> {
+fn links(fragment: &Html) -> Vec<ElementRef<'_>> {
     get_elems(fragment, "a")
 }
 
 #[allow(dead_code)]
-fn h1s(fragment: &Html) -> Vec<ElementRef> {
+fn h1s(fragment: &Html) -> Vec<ElementRef<'_>> {
     get_elems(fragment, "h1")
 }
 
@@ -194,13 +443,17 @@ mod test {
         assert_eq!(
             search_results[0],
             SearchResult { 
-                url: "/url?q=https://www.foxsports.com/nfl/david-blough",
+                url: "/url?q=https://www.foxsports.com/nfl/david-blough".into(),
                 title: Some(vec![
-                    "David Blough - NFL News, Rumors, & Updates - FOX Sports",
+                    "David Blough - NFL News, Rumors, & Updates - FOX Sports".into(),
                 ]),
                 description: Some(vec![
-                    "Remains No. 3 QB Blough (coach's decision) is inactive for Thursday's game against the Bears. Impact While dressing as the No.",
+                    "Remains No. 3 QB Blough (coach's decision) is inactive for Thursday's game against the Bears. Impact While dressing as the No.".into(),
                 ]),
+                display_url: Some(
+                    "www.foxsports.com › nfl › david-blough-player".into()
+                ),
+                ..SearchResult::new("")
             }
         );       
     }
@@ -250,20 +503,96 @@ mod test {
         assert_eq!(
             search_results[0],
             SearchResult { 
-                url: "/url?q=https://www.cbssports.com/mlb/teams/",
+                url: "/url?q=https://www.cbssports.com/mlb/teams/".into(),
                 title: Some(vec![
-                    "Chicago Cubs News, Schedule - MLB - CBS Sports",
+                    "Chicago Cubs News, Schedule - MLB - CBS Sports".into(),
                 ]),
                 description: Some(vec![
-                    "21 hours ago",
-                    " · ",
-                    "Get the latest news and information for the Chicago Cubs. 2022 season schedule, scores, stats, and highlights. Find out the latest on your favorite MLB ...",
+                    "21 hours ago".into(),
+                    " · ".into(),
+                    "Get the latest news and information for the Chicago Cubs. 2022 season schedule, scores, stats, and highlights. Find out the latest on your favorite MLB ...".into(),
                 ]),
+                display_url: Some(
+                    "www.cbssports.com › mlb › teams › CHC › chicago-cubs".into()
+                ),
+                ..SearchResult::new("")
             }
         );       
     }
 
 
+    #[test]
+    fn test_parse_reads_rich_snippet() {
+        let page = concat!(
+            "<body><div>",
+            r#"<a href="/url?q=https://www.mlb.com/cubs&amp;sa=U">"#,
+            "<h3><div>Official Chicago Cubs Website</div></h3>",
+            "<div>www.mlb.com &#8250; cubs</div>",
+            "</a>",
+            "<div><span>21 hours ago &#183; ",
+            "Get the latest news on the Chicago <em>Cubs</em></span></div>",
+            "<div><span>Rating: 4.5 · 1,234 reviews</span></div>",
+            "<div><span>$19.99</span></div>",
+            r#"<a href="/url?q=https://www.mlb.com/cubs/roster&amp;sa=U">"#,
+            "Roster</a>",
+            r#"<a href="https://www.mlb.com/cubs/schedule">Schedule</a>"#,
+            r#"<a href="/search?q=cubs&amp;start=10">Next</a>"#,
+            "</div></body>",
+        );
+
+        let dom = Html::parse_document(page);
+        let results = parse(&dom).unwrap();
+        let result = &results[0];
+
+        assert_eq!(
+            result.display_url.as_deref(),
+            Some("www.mlb.com › cubs")
+        );
+        assert_eq!(result.date.as_deref(), Some("21 hours ago"));
+        assert_eq!(
+            result.snippet.as_deref(),
+            Some("Get the latest news on the Chicago Cubs")
+        );
+        assert_eq!(
+            result.annotations,
+            vec!["Rating: 4.5 · 1,234 reviews", "$19.99"]
+        );
+        assert_eq!(
+            result
+                .sitelinks
+                .iter()
+                .map(|sitelink| &*sitelink.title)
+                .collect::<Vec<_>>(),
+            vec!["Roster", "Schedule"]
+        );
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].url, "/search?q=cubs&start=10");
+    }
+
+    #[test]
+    fn test_read_snippet_splits_date_from_text() {
+        let mut result = SearchResult::new("https://www.cbssports.com/");
+        result.add_to_description(vec![
+            "Jan 5, 2023 · Get the latest news.\u{a0}...",
+        ]);
+
+        read_snippet(&mut result);
+
+        assert_eq!(result.date.as_deref(), Some("Jan 5, 2023"));
+        assert_eq!(result.snippet.as_deref(), Some("Get the latest news. ..."));
+        assert!(matches!(result.date, Some(Cow::Borrowed(_))));
+    }
+
+    #[test]
+    fn test_looks_like_date() {
+        assert!(looks_like_date("21 hours ago"));
+        assert!(looks_like_date(" 5 Sept. 2023 "));
+        assert!(looks_like_date("2023-01-05"));
+        assert!(!looks_like_date("Decide 2023"));
+        assert!(!looks_like_date("Ad"));
+        assert!(!looks_like_date("The cubs won 3 games ago"));
+    }
+
     #[test]
     fn test_get_text() {
         let html = "<h1>Hello World</h1>";
@@ -0,0 +1,371 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use url::{form_urlencoded, Url};
+
+use crate::{search_request::is_locale_code, url_cleaner::is_on_domain};
+
+// Keeps the cookie well under the 4KB browsers store per cookie.
+const MAX_SITES: usize = 50;
+const COOKIE_MAX_AGE: &str = "Max-Age=31536000";
+
+/// A user's own adjustments to their results: sites to hide, sites to move
+/// to the top, and the language and region to search in. Kept in a cookie
+/// so serving them needs no storage.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preferences {
+    /// Domains whose results are dropped, subdomains included.
+    pub blocked: Vec<String>,
+    /// Domains whose results come first, subdomains included.
+    pub pinned: Vec<String>,
+    /// `hl` for searches that don't set one.
+    pub language: Option<String>,
+    /// `gl` for searches that don't set one.
+    pub region: Option<String>,
+    /// `lr` for searches that don't set one, e.g. `lang_fr`.
+    pub result_language: Option<String>,
+}
+
+impl Preferences {
+    pub const COOKIE_NAME: &'static str = "google2005_prefs";
+
+    /// Reads the preferences from a request's `Cookie` header, ignoring
+    /// other cookies and any sites that aren't domain names.
+    pub fn from_cookie_header(header: &str) -> Preferences {
+        cookie(header, Self::COOKIE_NAME)
+            .map(Self::from_form)
+            .unwrap_or_default()
+    }
+
+    /// Reads `blocked`, `pinned`, `hl`, `gl` and `lr` from form fields such
+    /// as a preferences form's query string; sites are separated by
+    /// whitespace or commas.
+    pub fn from_form(form: &str) -> Preferences {
+        let mut preferences = Preferences::default();
+
+        for (key, value) in form_urlencoded::parse(form.as_bytes()) {
+            let sites = match key.as_ref() {
+                "blocked" => &mut preferences.blocked,
+                "pinned" => &mut preferences.pinned,
+                "hl" => {
+                    preferences.language = Self::locale(&value);
+                    continue;
+                }
+                "gl" => {
+                    preferences.region = Self::locale(&value);
+                    continue;
+                }
+                "lr" => {
+                    preferences.result_language =
+                        Self::locale(&value).map(|lr| {
+                            if lr.starts_with("lang_") {
+                                lr
+                            } else {
+                                format!("lang_{}", lr)
+                            }
+                        });
+                    continue;
+                }
+                _ => continue,
+            };
+
+            for site in value.split(|c: char| c.is_whitespace() || c == ',') {
+                if let Some(site) = Self::site(site) {
+                    if !sites.contains(&site) && sites.len() < MAX_SITES {
+                        sites.push(site);
+                    }
+                }
+            }
+        }
+
+        preferences
+    }
+
+    /// The `Set-Cookie` value that stores these preferences for a year.
+    pub fn to_set_cookie(&self) -> String {
+        let mut value = form_urlencoded::Serializer::new(String::new());
+        value
+            .append_pair("blocked", &self.blocked.join(" "))
+            .append_pair("pinned", &self.pinned.join(" "));
+
+        let locale = [
+            ("hl", &self.language),
+            ("gl", &self.region),
+            ("lr", &self.result_language),
+        ];
+        for (key, code) in locale {
+            if let Some(code) = code {
+                value.append_pair(key, code);
+            }
+        }
+
+        format!(
+            "{}={}; Path=/; {}; SameSite=Lax; HttpOnly",
+            Self::COOKIE_NAME,
+            value.finish(),
+            COOKIE_MAX_AGE
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocked.is_empty()
+            && self.pinned.is_empty()
+            && self.language.is_none()
+            && self.region.is_none()
+            && self.result_language.is_none()
+    }
+
+    /// Adds `site` to the blocked sites, and takes it off the pinned ones.
+    /// Returns false when `site` isn't a domain name.
+    pub fn block(&mut self, site: &str) -> bool {
+        let site = match Self::site(site) {
+            Some(site) => site,
+            None => return false,
+        };
+
+        self.pinned.retain(|pinned| *pinned != site);
+        if !self.blocked.contains(&site) && self.blocked.len() < MAX_SITES {
+            self.blocked.push(site);
+        }
+
+        true
+    }
+
+    pub fn is_blocked(&self, url: &str) -> bool {
+        Self::is_on_any(url, &self.blocked)
+    }
+
+    pub fn is_pinned(&self, url: &str) -> bool {
+        Self::is_on_any(url, &self.pinned)
+    }
+
+    fn is_on_any(url: &str, sites: &[String]) -> bool {
+        if sites.is_empty() {
+            return false;
+        }
+
+        let host = match Url::parse(url) {
+            Ok(url) => url.host_str().map(str::to_ascii_lowercase),
+            Err(_) => None,
+        };
+
+        host.is_some_and(|host| sites.iter().any(|s| is_on_domain(&host, s)))
+    }
+
+    fn locale(input: &str) -> Option<String> {
+        let input = input.trim();
+
+        if is_locale_code(input) {
+            Some(input.to_string())
+        } else {
+            None
+        }
+    }
+
+    /// The domain name a user means by `input`, e.g. `pinterest.com` for
+    /// `https://www.Pinterest.com/ideas/`.
+    pub fn site(input: &str) -> Option<String> {
+        let input = input.trim().to_ascii_lowercase();
+        let host = match input.split_once("://") {
+            Some((_, rest)) => rest,
+            None => &input,
+        };
+        let host = host.split(&['/', '?', '#', ':'][..]).next()?;
+        let host = host.strip_prefix("www.").unwrap_or(host);
+
+        let is_domain = host.contains('.')
+            && !host.starts_with('.')
+            && !host.ends_with('.')
+            && host
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+
+        if is_domain {
+            Some(host.to_string())
+        } else {
+            None
+        }
+    }
+}
+
+/// Guards the forms that change preferences against other sites posting
+/// them. The forms carry the token from a `SameSite=Strict` cookie, which
+/// browsers leave off requests that other sites start, and a post is only
+/// accepted when the two match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormToken {
+    pub value: String,
+    // Made for this request, so not in the browser's cookie yet.
+    fresh: bool,
+}
+
+impl FormToken {
+    pub const COOKIE_NAME: &'static str = "google2005_form";
+    /// The form field holding the token.
+    pub const FIELD_NAME: &'static str = "token";
+
+    /// The token from a request's `Cookie` header, or a new one when it has
+    /// none.
+    pub fn from_cookie_header(header: Option<&str>) -> FormToken {
+        match header
+            .and_then(|header| cookie(header, Self::COOKIE_NAME))
+            .filter(|value| !value.is_empty())
+        {
+            Some(value) => FormToken {
+                value: value.to_string(),
+                fresh: false,
+            },
+            None => FormToken {
+                value: Self::random(),
+                fresh: true,
+            },
+        }
+    }
+
+    /// The `Set-Cookie` value that gives the browser a new token, or None
+    /// when it already has this one.
+    pub fn to_set_cookie(&self) -> Option<String> {
+        self.fresh.then(|| {
+            format!(
+                "{}={}; Path=/; {}; SameSite=Strict; HttpOnly",
+                Self::COOKIE_NAME,
+                self.value,
+                COOKIE_MAX_AGE
+            )
+        })
+    }
+
+    /// Whether the posted `form` came from one of our pages: its token is
+    /// the one the browser sent in its cookie.
+    pub fn accepts(&self, form: &str) -> bool {
+        !self.fresh
+            && form_urlencoded::parse(form.as_bytes()).any(|(key, value)| {
+                key == Self::FIELD_NAME && value == self.value
+            })
+    }
+
+    // 128 bits from std's `RandomState`, which the OS seeds randomly.
+    fn random() -> String {
+        (0..2)
+            .map(|_| RandomState::new().build_hasher().finish())
+            .map(|bits| format!("{:016x}", bits))
+            .collect()
+    }
+}
+
+// The value of the cookie `name` in a `Cookie` header.
+fn cookie<'h>(header: &'h str, name: &str) -> Option<&'h str> {
+    header
+        .split(';')
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_site() {
+        assert_eq!(
+            Preferences::site("https://www.Pinterest.com/ideas/").as_deref(),
+            Some("pinterest.com")
+        );
+        assert_eq!(
+            Preferences::site("en.wikipedia.org").as_deref(),
+            Some("en.wikipedia.org")
+        );
+        assert_eq!(Preferences::site("localhost"), None);
+        assert_eq!(Preferences::site("<script>.com"), None);
+    }
+
+    #[test]
+    fn test_cookie_round_trip() {
+        let preferences = Preferences::from_form(concat!(
+            "blocked=pinterest.com%0D%0Aquora.com,+pinterest.com",
+            "&pinned=mlb.com"
+        ));
+
+        assert_eq!(preferences.blocked, vec!["pinterest.com", "quora.com"]);
+        assert_eq!(preferences.pinned, vec!["mlb.com"]);
+
+        let set_cookie = preferences.to_set_cookie();
+        let cookie = set_cookie.split(';').next().unwrap();
+        assert!(!cookie.contains(','));
+        assert_eq!(
+            Preferences::from_cookie_header(&format!("theme=dark; {}", cookie)),
+            preferences
+        );
+    }
+
+    #[test]
+    fn test_locale_round_trip() {
+        let preferences = Preferences::from_form("hl=pt-BR&gl=br&lr=pt");
+
+        assert_eq!(preferences.language.as_deref(), Some("pt-BR"));
+        assert_eq!(preferences.region.as_deref(), Some("br"));
+        assert_eq!(preferences.result_language.as_deref(), Some("lang_pt"));
+        assert!(!preferences.is_empty());
+
+        let set_cookie = preferences.to_set_cookie();
+        let cookie = set_cookie.split(';').next().unwrap();
+        assert_eq!(Preferences::from_cookie_header(cookie), preferences);
+    }
+
+    #[test]
+    fn test_ignores_bad_locales() {
+        let preferences = Preferences::from_form("hl=&gl=%3Cb%3E&lr=+");
+
+        assert!(preferences.is_empty());
+    }
+
+    #[test]
+    fn test_matches_subdomains() {
+        let preferences = Preferences::from_form("blocked=pinterest.com");
+
+        assert!(preferences.is_blocked("https://www.pinterest.com/pin/1"));
+        assert!(preferences.is_blocked("https://uk.pinterest.com/"));
+        assert!(!preferences.is_blocked("https://notpinterest.com/"));
+        assert!(!preferences.is_pinned("https://www.pinterest.com/"));
+    }
+
+    #[test]
+    fn test_block_unpins() {
+        let mut preferences = Preferences::from_form("pinned=quora.com");
+
+        assert!(preferences.block("https://www.quora.com/answer"));
+        assert!(!preferences.block("not a site"));
+        assert_eq!(preferences.blocked, vec!["quora.com"]);
+        assert!(preferences.pinned.is_empty());
+    }
+
+    #[test]
+    fn test_new_form_token() {
+        let token = FormToken::from_cookie_header(Some("theme=dark"));
+        let set_cookie = token.to_set_cookie().unwrap();
+
+        assert_eq!(token.value.len(), 32);
+        assert!(set_cookie
+            .starts_with(&format!("google2005_form={};", token.value)));
+        assert!(set_cookie.contains("SameSite=Strict"));
+        // Every page with a form must see it, or it would mint another
+        assert!(set_cookie.contains("Path=/;"));
+        assert_ne!(FormToken::from_cookie_header(None).value, token.value);
+        // Nothing to compare a new token with
+        assert!(!token.accepts(&format!("token={}", token.value)));
+    }
+
+    #[test]
+    fn test_form_token_from_cookie() {
+        let token = FormToken::from_cookie_header(Some(
+            "google2005_prefs=blocked=; google2005_form=0123abcd",
+        ));
+
+        assert_eq!(token.value, "0123abcd");
+        assert_eq!(token.to_set_cookie(), None);
+        assert!(token.accepts("site=mlb.com&token=0123abcd"));
+        assert!(!token.accepts("site=mlb.com&token=4567ef01"));
+        assert!(!token.accepts("site=mlb.com"));
+    }
+}
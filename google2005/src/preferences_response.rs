@@ -0,0 +1,44 @@
+use askama::Template;
+
+use crate::{
+    preferences::{FormToken, Preferences},
+    utils::hostnames,
+};
+
+/// The form for editing the sites a user blocks and pins, and the language
+/// and region they search in.
+#[derive(Debug, Template)]
+#[template(path = "preferences.html")]
+pub struct PreferencesResponse {
+    blocked: String,
+    pinned: String,
+    language: String,
+    region: String,
+    result_language: String,
+    /// Proves to `/preferences` that the form was posted from this page.
+    form_token: String,
+    image_hostname: String,
+    stylesheet_hostname: String,
+}
+
+impl PreferencesResponse {
+    pub fn new(
+        preferences: &Preferences,
+        form_token: &FormToken,
+    ) -> PreferencesResponse {
+        PreferencesResponse {
+            blocked: preferences.blocked.join("\n"),
+            pinned: preferences.pinned.join("\n"),
+            language: preferences.language.clone().unwrap_or_default(),
+            region: preferences.region.clone().unwrap_or_default(),
+            result_language: preferences
+                .result_language
+                .as_deref()
+                .map(|lr| lr.trim_start_matches("lang_").to_string())
+                .unwrap_or_default(),
+            form_token: form_token.value.clone(),
+            image_hostname: hostnames::image_hostname(),
+            stylesheet_hostname: hostnames::stylesheet_hostname(),
+        }
+    }
+}
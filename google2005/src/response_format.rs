@@ -0,0 +1,109 @@
+/// How a `/search` response is rendered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResponseFormat {
+    #[default]
+    Html,
+    Rss,
+    Atom,
+}
+
+impl ResponseFormat {
+    /// Picks the `requested` format, i.e. the search's `format=`, falling
+    /// back to the first media type in the `Accept` header that we can
+    /// produce. Quality values are ignored; browsers list `text/html` first
+    /// anyway. Responses picked this way vary with `Accept`.
+    pub fn negotiate(
+        requested: Option<ResponseFormat>,
+        accept: Option<&str>,
+    ) -> ResponseFormat {
+        if let Some(format) = requested {
+            return format;
+        }
+
+        accept
+            .unwrap_or_default()
+            .split(',')
+            .map(|media_type| media_type.split(';').next().unwrap().trim())
+            .find_map(|media_type| match media_type {
+                "text/html" | "*/*" => Some(ResponseFormat::Html),
+                "application/rss+xml" => Some(ResponseFormat::Rss),
+                "application/atom+xml" => Some(ResponseFormat::Atom),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn from_param(value: &str) -> Option<ResponseFormat> {
+        match value {
+            "html" => Some(ResponseFormat::Html),
+            "rss" => Some(ResponseFormat::Rss),
+            "atom" => Some(ResponseFormat::Atom),
+            _ => None,
+        }
+    }
+
+    /// The `format=` value that selects this format.
+    pub fn param(&self) -> &'static str {
+        match self {
+            ResponseFormat::Html => "html",
+            ResponseFormat::Rss => "rss",
+            ResponseFormat::Atom => "atom",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ResponseFormat::Html => "text/html; charset=UTF-8",
+            ResponseFormat::Rss => "application/rss+xml",
+            ResponseFormat::Atom => "application/atom+xml",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_defaults_to_html() {
+        assert_eq!(ResponseFormat::negotiate(None, None), ResponseFormat::Html);
+    }
+
+    #[test]
+    fn test_format_param() {
+        assert_eq!(
+            ResponseFormat::negotiate(ResponseFormat::from_param("rss"), None),
+            ResponseFormat::Rss
+        );
+        assert_eq!(ResponseFormat::from_param("pdf"), None);
+    }
+
+    #[test]
+    fn test_format_param_beats_accept_header() {
+        assert_eq!(
+            ResponseFormat::negotiate(
+                Some(ResponseFormat::Atom),
+                Some("application/rss+xml")
+            ),
+            ResponseFormat::Atom
+        );
+    }
+
+    #[test]
+    fn test_accept_header() {
+        assert_eq!(
+            ResponseFormat::negotiate(
+                None,
+                Some("application/atom+xml;q=0.9, application/xml;q=0.8")
+            ),
+            ResponseFormat::Atom
+        );
+        assert_eq!(
+            ResponseFormat::negotiate(
+                None,
+                Some("text/html,application/xhtml+xml,*/*;q=0.8")
+            ),
+            ResponseFormat::Html
+        );
+    }
+}
@@ -0,0 +1,984 @@
+use std::sync::Arc;
+
+use askama::Template;
+use url::{form_urlencoded, Url};
+
+use crate::{
+    api_response::ApiResponse,
+    fetcher::SearchFetcher,
+    filter_explanation_response::FilterExplanationResponse,
+    filter_rules::FilterRules,
+    home_page_response::HomePageResponse,
+    open_search_response::OpenSearchResponse,
+    preferences::{FormToken, Preferences},
+    preferences_response::PreferencesResponse,
+    response_format::ResponseFormat,
+    search_request::SearchRequest,
+    search_results_response::SearchResultsResponse,
+    utils::{google2005_error::Google2005Error, hostnames, http_status},
+};
+
+const HTML_CONTENT_TYPE: &str = "text/html; charset=UTF-8";
+const TEXT_CONTENT_TYPE: &str = "text/plain; charset=UTF-8";
+const JSON_CONTENT_TYPE: &str = "application/json";
+/// The response header summarising a results page's `HealthReport`.
+pub const HEALTH_HEADER: &str = "X-Google2005-Health";
+
+// (file name, content type, contents) of everything the pages link to, for
+// servers that don't have the assets hosted elsewhere.
+const ASSETS: &[(&str, &str, &[u8])] = &[
+    (
+        "search.css",
+        "text/css",
+        include_bytes!("client/stylesheets/search.css"),
+    ),
+    ("logo.png", "image/png", include_bytes!("client/images/logo.png")),
+    ("two.png", "image/png", include_bytes!("client/images/two.png")),
+    ("five.png", "image/png", include_bytes!("client/images/five.png")),
+    (
+        "betterredzero.png",
+        "image/png",
+        include_bytes!("client/images/betterredzero.png"),
+    ),
+    (
+        "betteryellowzero.png",
+        "image/png",
+        include_bytes!("client/images/betteryellowzero.png"),
+    ),
+];
+
+/// A request as the router sees it, whichever server received it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: Vec<(String, String)>,
+    /// E.g. a posted form.
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// `target` is the request-target from the request line, e.g.
+    /// `/search?q=cubs&start=10`.
+    pub fn new(method: &str, target: &str) -> HttpRequest {
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query.to_string())),
+            None => (target, None),
+        };
+
+        HttpRequest {
+            method: method.to_ascii_uppercase(),
+            path: path.to_string(),
+            query,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> HttpRequest {
+        self.headers.push((name.to_string(), value.to_string()));
+
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> HttpRequest {
+        self.body = body.into();
+
+        self
+    }
+
+    /// The first value of the header `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+/// What the server should send back, whichever server it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new(status: u16) -> HttpResponse {
+        HttpResponse {
+            status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> HttpResponse {
+        self.headers.push((name.to_string(), value.to_string()));
+
+        self
+    }
+
+    pub fn with_body(
+        self,
+        content_type: &str,
+        body: impl Into<Vec<u8>>,
+    ) -> HttpResponse {
+        let mut response = self.with_header("Content-Type", content_type);
+        response.body = body.into();
+
+        response
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// Reason phrase to go with `status`.
+    pub fn reason(&self) -> &'static str {
+        http_status::reason_phrase(self.status)
+    }
+}
+
+fn find_header<'h>(
+    headers: &'h [(String, String)],
+    name: &str,
+) -> Option<&'h str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Routes requests to the pages, so every deployment behaves the same and
+/// only has to translate its own request and response types.
+///
+/// `fetcher` gets the Google results page for each search.
+pub struct Router<'f> {
+    site_hostname: String,
+    fetcher: &'f dyn SearchFetcher,
+    filter_rules: Arc<FilterRules>,
+}
+
+impl<'f> Router<'f> {
+    pub fn new(fetcher: &'f dyn SearchFetcher) -> Router<'f> {
+        Router {
+            site_hostname: hostnames::site_hostname(),
+            fetcher,
+            filter_rules: Arc::new(FilterRules::default()),
+        }
+    }
+
+    /// Serve links (e.g. in `/opensearch.xml`) pointing at `site_hostname`
+    /// instead of the deployed site. `site_hostname` ends with a `/`.
+    pub fn with_site_hostname(mut self, site_hostname: &str) -> Router<'f> {
+        self.site_hostname = site_hostname.to_string();
+
+        self
+    }
+
+    /// Filter results with `filter_rules` instead of the default rules.
+    /// Servers handling many requests can share one `Arc` of them.
+    pub fn with_filter_rules(
+        mut self,
+        filter_rules: impl Into<Arc<FilterRules>>,
+    ) -> Router<'f> {
+        self.filter_rules = filter_rules.into();
+
+        self
+    }
+
+    pub fn handle(&self, request: &HttpRequest) -> HttpResponse {
+        let mut response = self.respond(request);
+
+        // A HEAD response describes the GET one, without its body
+        if request.method == "HEAD" {
+            let length = response.body.len().to_string();
+            response = response.with_header("Content-Length", &length);
+            response.body.clear();
+        }
+
+        response
+    }
+
+    /// Like `handle`, but keeps the body of `HEAD` responses, for servers
+    /// that work out the headers from it and leave it out themselves.
+    pub fn respond(&self, request: &HttpRequest) -> HttpResponse {
+        match request.method.as_str() {
+            "GET" | "HEAD" => self.route(request),
+            "POST" => Self::route_form(request),
+            _ => Self::method_not_allowed("GET, HEAD"),
+        }
+    }
+
+    // Only forms that change preferences are posted, so that following a
+    // link never changes them.
+    fn route_form(request: &HttpRequest) -> HttpResponse {
+        match request.path.as_str() {
+            "/preferences" => Self::save_preferences(request),
+            "/preferences/block" => Self::block_site(request),
+            _ => Self::method_not_allowed("GET, HEAD"),
+        }
+    }
+
+    fn method_not_allowed(allow: &str) -> HttpResponse {
+        HttpResponse::new(405)
+            .with_header("Allow", allow)
+            .with_body(TEXT_CONTENT_TYPE, "This method is not allowed\n")
+    }
+
+    fn route(&self, request: &HttpRequest) -> HttpResponse {
+        match request.path.as_str() {
+            "/" => {
+                Self::page(HomePageResponse::new().render(), HTML_CONTENT_TYPE)
+            }
+            "/opensearch.xml" => Self::page(
+                OpenSearchResponse::for_site(&self.site_hostname).render(),
+                OpenSearchResponse::CONTENT_TYPE,
+            ),
+            "/search" => self.search_page(request),
+            "/api/search" => self.api_search(request),
+            "/preferences" => Self::preferences_page(request),
+            "/preferences/block" => Self::method_not_allowed("POST"),
+            path => Self::asset(path).unwrap_or_else(|| {
+                HttpResponse::new(404).with_body(
+                    TEXT_CONTENT_TYPE,
+                    format!("The requested page {} could not be found\n", path),
+                )
+            }),
+        }
+    }
+
+    fn search_page(&self, request: &HttpRequest) -> HttpResponse {
+        let search_request = match Self::search_request(request) {
+            Ok(search_request) => search_request,
+            Err(e) => return Self::search_failed(e, None),
+        };
+
+        if search_request.debug {
+            return self.explanation_page(request, &search_request);
+        }
+
+        let format = ResponseFormat::negotiate(
+            search_request.format,
+            request.header("Accept"),
+        );
+
+        let form_token = Self::form_token(request);
+        let rendered =
+            self.search(request, &search_request).and_then(|search| {
+                let search = search.with_form_token(&form_token);
+
+                Ok((search.render_as(format)?, search.health().summary()))
+            });
+
+        match rendered {
+            // Caches mustn't serve a feed to a browser, or a page to a feed
+            // reader
+            Ok((contents, health)) => Self::with_form_token(
+                HttpResponse::new(200)
+                    .with_header(HEALTH_HEADER, &health)
+                    .with_header("Vary", "Accept")
+                    .with_body(format.content_type(), contents),
+                &form_token,
+            ),
+            Err(e) => Self::search_failed(e, Some(&search_request)),
+        }
+    }
+
+    // Sends the user to the same search on google.com when it's the search
+    // engine that failed us.
+    fn search_failed(
+        e: Google2005Error,
+        search_request: Option<&SearchRequest>,
+    ) -> HttpResponse {
+        println!("search failed: {}", e.details());
+
+        match search_request {
+            Some(search_request) if e.falls_back_to_google() => {
+                HttpResponse::new(302).with_header(
+                    "Location",
+                    &search_request.upstream_url(search_request.start),
+                )
+            }
+            _ => HttpResponse::new(e.status_code())
+                .with_body(HTML_CONTENT_TYPE, e.to_string()),
+        }
+    }
+
+    /// Answers with the versioned JSON document, including errors, rather
+    /// than falling back to google.com.
+    fn api_search(&self, request: &HttpRequest) -> HttpResponse {
+        let result = match Self::search_request(request) {
+            Ok(search_request) if search_request.debug => {
+                return self.api_explanation(request, &search_request)
+            }
+            Ok(search_request) => self.search(request, &search_request),
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = &result {
+            println!("api search failed: {}", e.details());
+        }
+
+        let api_response = ApiResponse::new(&result);
+        let mut response = HttpResponse::new(api_response.status_code());
+
+        if let Ok(search) = &result {
+            response =
+                response.with_header(HEALTH_HEADER, &search.health().summary());
+        }
+
+        response.with_body(JSON_CONTENT_TYPE, api_response.to_json())
+    }
+
+    fn search(
+        &self,
+        request: &HttpRequest,
+        search_request: &SearchRequest,
+    ) -> Result<SearchResultsResponse, Google2005Error> {
+        crate::search(
+            search_request,
+            self.fetcher,
+            &self.filter_rules,
+            &Self::preferences(request),
+        )
+    }
+
+    /// Shows what the filter did with every result, rather than falling
+    /// back to google.com when the search fails.
+    fn explanation_page(
+        &self,
+        request: &HttpRequest,
+        search_request: &SearchRequest,
+    ) -> HttpResponse {
+        match self.explain(request, search_request) {
+            Ok(explanation) => {
+                Self::page(explanation.render(), HTML_CONTENT_TYPE)
+            }
+            Err(e) => {
+                println!("filter explanation failed: {}", e.details());
+
+                HttpResponse::new(e.status_code())
+                    .with_body(HTML_CONTENT_TYPE, e.to_string())
+            }
+        }
+    }
+
+    fn api_explanation(
+        &self,
+        request: &HttpRequest,
+        search_request: &SearchRequest,
+    ) -> HttpResponse {
+        match self.explain(request, search_request) {
+            Ok(explanation) => HttpResponse::new(200)
+                .with_body(JSON_CONTENT_TYPE, explanation.to_json()),
+            Err(e) => {
+                println!("api filter explanation failed: {}", e.details());
+
+                let result = Err(e);
+                let api_response = ApiResponse::new(&result);
+
+                HttpResponse::new(api_response.status_code())
+                    .with_body(JSON_CONTENT_TYPE, api_response.to_json())
+            }
+        }
+    }
+
+    fn explain(
+        &self,
+        request: &HttpRequest,
+        search_request: &SearchRequest,
+    ) -> Result<FilterExplanationResponse, Google2005Error> {
+        crate::explain_search(
+            search_request,
+            self.fetcher,
+            &self.filter_rules,
+            &Self::preferences(request),
+        )
+    }
+
+    // The search in the query string, in the user's language and region
+    // unless it names its own.
+    fn search_request(
+        request: &HttpRequest,
+    ) -> Result<SearchRequest, Google2005Error> {
+        let mut search_request =
+            SearchRequest::parse(request.query.as_deref().unwrap_or_default())?;
+        search_request.default_locale(
+            &Self::preferences(request),
+            request.header("Accept-Language"),
+        );
+
+        Ok(search_request)
+    }
+
+    /// Shows the user's preferences in a form that posts them back here.
+    fn preferences_page(request: &HttpRequest) -> HttpResponse {
+        let form_token = Self::form_token(request);
+        let page = PreferencesResponse::new(
+            &Self::preferences(request),
+            &form_token,
+        );
+
+        Self::with_form_token(
+            Self::page(page.render(), HTML_CONTENT_TYPE),
+            &form_token,
+        )
+    }
+
+    /// Saves the posted preferences to the preferences cookie.
+    fn save_preferences(request: &HttpRequest) -> HttpResponse {
+        let form = String::from_utf8_lossy(&request.body);
+        if !Self::form_token(request).accepts(&form) {
+            return Self::forbidden_form();
+        }
+
+        let preferences = Preferences::from_form(&form);
+
+        HttpResponse::new(303)
+            .with_header("Set-Cookie", &preferences.to_set_cookie())
+            .with_header("Location", "/preferences")
+    }
+
+    /// Adds the posted `site` to the blocked sites, then sends the user back
+    /// to `return`, e.g. the search they blocked it from.
+    fn block_site(request: &HttpRequest) -> HttpResponse {
+        let form = String::from_utf8_lossy(&request.body);
+        if !Self::form_token(request).accepts(&form) {
+            return Self::forbidden_form();
+        }
+
+        let params =
+            form_urlencoded::parse(form.as_bytes()).collect::<Vec<_>>();
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_ref())
+        };
+
+        let mut preferences = Self::preferences(request);
+        if !preferences.block(param("site").unwrap_or_default()) {
+            return HttpResponse::new(400)
+                .with_body(TEXT_CONTENT_TYPE, "That is not a site\n");
+        }
+
+        let location = param("return")
+            .and_then(Self::own_page)
+            .unwrap_or_else(|| "/preferences".to_string());
+
+        HttpResponse::new(303)
+            .with_header("Set-Cookie", &preferences.to_set_cookie())
+            .with_header("Location", &location)
+    }
+
+    // The path and query `target` leads to, when that's one of our own
+    // pages. It's read as a browser would, so `//host` and `/\host` are
+    // other sites.
+    fn own_page(target: &str) -> Option<String> {
+        let site = Url::parse("http://google2005.invalid/").unwrap();
+        let url = site.join(target).ok()?;
+        if url.origin() != site.origin() {
+            return None;
+        }
+
+        Some(match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        })
+    }
+
+    fn preferences(request: &HttpRequest) -> Preferences {
+        request
+            .header("Cookie")
+            .map(Preferences::from_cookie_header)
+            .unwrap_or_default()
+    }
+
+    fn form_token(request: &HttpRequest) -> FormToken {
+        FormToken::from_cookie_header(request.header("Cookie"))
+    }
+
+    // Gives the browser the token that `response`'s forms post, unless it
+    // has it already.
+    fn with_form_token(
+        response: HttpResponse,
+        form_token: &FormToken,
+    ) -> HttpResponse {
+        match form_token.to_set_cookie() {
+            Some(set_cookie) => response.with_header("Set-Cookie", &set_cookie),
+            None => response,
+        }
+    }
+
+    // A form posted from another site, or from a page older than the
+    // browser's token.
+    fn forbidden_form() -> HttpResponse {
+        HttpResponse::new(403).with_body(
+            TEXT_CONTENT_TYPE,
+            "This form has expired. Reload the page and try again.\n",
+        )
+    }
+
+    fn page(
+        rendered: Result<String, askama::Error>,
+        content_type: &str,
+    ) -> HttpResponse {
+        match rendered {
+            Ok(contents) => {
+                HttpResponse::new(200).with_body(content_type, contents)
+            }
+            Err(e) => {
+                let e = Google2005Error::from(e);
+                println!("render failed: {}", e.details());
+
+                HttpResponse::new(e.status_code())
+                    .with_body(TEXT_CONTENT_TYPE, e.to_string())
+            }
+        }
+    }
+
+    fn asset(path: &str) -> Option<HttpResponse> {
+        let file_name = path.rsplit('/').next()?;
+
+        ASSETS
+            .iter()
+            .find(|(name, _, _)| *name == file_name)
+            .map(|(_, content_type, contents)| {
+                HttpResponse::new(200).with_body(content_type, *contents)
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fetcher::{FetchedPage, ReplayFetcher};
+
+    const RESULTS_PAGE: &str = concat!(
+        "<html><head><title>cubs - Google Search</title></head><body>",
+        r#"<a href="https://www.mlb.com/cubs"><h3>Chicago Cubs</h3></a>"#,
+        "<span>The official site of the Chicago Cubs</span>",
+        "</body></html>"
+    );
+
+    struct ResultsPage;
+
+    impl SearchFetcher for ResultsPage {
+        fn fetch(&self, url: &str) -> Result<FetchedPage, Google2005Error> {
+            // Our second page, reached without a next page link, is read
+            // from Google's second and third
+            assert!(
+                url == "https://www.google.com/search?q=cubs&start=10"
+                    || url == "https://www.google.com/search?q=cubs&start=20",
+                "fetched {}",
+                url
+            );
+
+            Ok(FetchedPage::new(200, RESULTS_PAGE))
+        }
+    }
+
+    struct RateLimited;
+
+    impl SearchFetcher for RateLimited {
+        fn fetch(&self, _url: &str) -> Result<FetchedPage, Google2005Error> {
+            Ok(FetchedPage::new(429, "Too Many Requests"))
+        }
+    }
+
+    fn get(target: &str) -> HttpResponse {
+        Router::new(&ResultsPage).handle(&HttpRequest::new("GET", target))
+    }
+
+    const TOKEN_COOKIE: &str = "google2005_form=0123abcd";
+
+    // Posts `form` as the browser would from one of our pages.
+    fn post(target: &str, form: &str) -> HttpResponse {
+        Router::new(&ResultsPage).handle(
+            &HttpRequest::new("POST", target)
+                .with_header("Cookie", TOKEN_COOKIE)
+                .with_body(format!("{}&token=0123abcd", form)),
+        )
+    }
+
+    #[test]
+    fn test_home_page() {
+        let response = get("/");
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("content-type"), Some(HTML_CONTENT_TYPE));
+    }
+
+    #[test]
+    fn test_rejects_other_methods() {
+        let response =
+            Router::new(&ResultsPage).handle(&HttpRequest::new("post", "/"));
+
+        assert_eq!(response.status, 405);
+        assert_eq!(response.reason(), "Method Not Allowed");
+        assert_eq!(response.header("Allow"), Some("GET, HEAD"));
+    }
+
+    #[test]
+    fn test_head_has_no_body() {
+        let response =
+            Router::new(&ResultsPage).handle(&HttpRequest::new("HEAD", "/"));
+
+        assert_eq!(response.status, 200);
+        assert!(response.body.is_empty());
+        assert_eq!(
+            response.header("Content-Length"),
+            Some(get("/").body.len().to_string().as_str())
+        );
+    }
+
+    #[test]
+    fn test_respond_keeps_head_body() {
+        let response =
+            Router::new(&ResultsPage).respond(&HttpRequest::new("HEAD", "/"));
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, get("/").body);
+    }
+
+    #[test]
+    fn test_unknown_page() {
+        assert_eq!(get("/nope").status, 404);
+    }
+
+    #[test]
+    fn test_assets() {
+        let response = get("/images/logo.png");
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("Content-Type"), Some("image/png"));
+        assert!(!response.body.is_empty());
+        assert_eq!(get("/search.css").header("Content-Type"), Some("text/css"));
+    }
+
+    #[test]
+    fn test_opensearch_uses_site_hostname() {
+        let response = Router::new(&ResultsPage)
+            .with_site_hostname("http://127.0.0.1:7878/")
+            .handle(&HttpRequest::new("GET", "/opensearch.xml"));
+        let body = String::from_utf8(response.body).unwrap();
+
+        assert!(body.contains("http://127.0.0.1:7878/search?q={searchTerms}"));
+    }
+
+    #[test]
+    fn test_search_page() {
+        let response = get("/search?q=cubs&start=10");
+        let body = String::from_utf8(response.body).unwrap();
+
+        assert_eq!(response.status, 200);
+        assert!(body.contains("https://www.mlb.com/cubs"));
+    }
+
+    #[test]
+    fn test_search_feed_from_accept_header() {
+        let request = HttpRequest::new("GET", "/search?q=cubs&start=10")
+            .with_header("Accept", "application/rss+xml");
+        let response = Router::new(&ResultsPage).handle(&request);
+
+        assert_eq!(
+            response.header("Content-Type"),
+            Some("application/rss+xml")
+        );
+        assert_eq!(response.header("Vary"), Some("Accept"));
+        assert_eq!(
+            get("/search?q=cubs&start=10").header("Vary"),
+            Some("Accept")
+        );
+    }
+
+    #[test]
+    fn test_search_without_query() {
+        let response = get("/search");
+
+        assert_eq!(response.status, 400);
+        assert_eq!(response.body, b"Please enter a query");
+    }
+
+    #[test]
+    fn test_search_rejects_bad_params() {
+        let response = get("/search?q=cubs&start=abc");
+
+        assert_eq!(response.status, 400);
+        assert_eq!(response.body, b"start must be a number from 0 to 990");
+    }
+
+    #[test]
+    fn test_search_falls_back_to_google() {
+        let response = Router::new(&RateLimited)
+            .handle(&HttpRequest::new("GET", "/search?q=cubs"));
+
+        assert_eq!(response.status, 302);
+        assert_eq!(
+            response.header("Location"),
+            Some("https://www.google.com/search?q=cubs")
+        );
+    }
+
+    #[test]
+    fn test_search_in_browser_language() {
+        let request = HttpRequest::new("GET", "/search?q=cubs")
+            .with_header("Accept-Language", "de-AT,de;q=0.9,en;q=0.5");
+        let response = Router::new(&RateLimited).handle(&request);
+
+        assert_eq!(
+            response.header("Location"),
+            Some("https://www.google.com/search?q=cubs&hl=de-AT&gl=at")
+        );
+    }
+
+    #[test]
+    fn test_search_in_preferred_language() {
+        let request = HttpRequest::new("GET", "/search?q=cubs&gl=ch")
+            .with_header("Cookie", "google2005_prefs=hl=fr&gl=fr&lr=lang_fr")
+            .with_header("Accept-Language", "de-AT");
+        let response = Router::new(&RateLimited).handle(&request);
+
+        assert_eq!(
+            response.header("Location"),
+            Some("https://www.google.com/search?q=cubs&hl=fr&gl=ch&lr=lang_fr")
+        );
+    }
+
+    #[test]
+    fn test_search_page_explains_filter() {
+        let response = get("/search?q=cubs&debug=1&start=10");
+        let body = String::from_utf8(response.body).unwrap();
+
+        assert_eq!(response.status, 200);
+        assert!(body.contains("Kept 1 of 2 results"));
+        assert!(body.contains(r#"<span class="filter-rule">kept</span>"#));
+    }
+
+    #[test]
+    fn test_api_search_explains_filter() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+        let response = Router::new(&ReplayFetcher::new(dir)).handle(
+            &HttpRequest::new("GET", "/api/search?q=chicago+cubs&debug=1"),
+        );
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("Content-Type"), Some(JSON_CONTENT_TYPE));
+
+        let body: serde_json::Value =
+            serde_json::from_slice(&response.body).unwrap();
+        let rules = body["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| result["rule"].as_str().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(body["kept"], 10);
+        assert_eq!(rules.iter().filter(|rule| **rule == "kept").count(), 10);
+        assert!(rules.contains(&"google_ad"));
+    }
+
+    #[test]
+    fn test_search_with_filter_rules() {
+        let rules = FilterRules::from_json(
+            r#"[{"name": "mlb", "when": {"host_in": ["mlb.com"]}}]"#,
+        )
+        .unwrap();
+        let response = Router::new(&ResultsPage)
+            .with_filter_rules(rules)
+            .handle(&HttpRequest::new("GET", "/search?q=cubs&start=10"));
+
+        assert_eq!(response.status, 404);
+    }
+
+    #[test]
+    fn test_search_page_has_block_site_forms() {
+        let response = get("/search?q=cubs&start=10");
+        let set_cookie = response.header("Set-Cookie").unwrap().to_string();
+        let body = String::from_utf8(response.body).unwrap();
+
+        assert!(body.contains(r#"action="/preferences/block""#));
+        assert!(body.contains(r#"name="site" value="mlb.com""#));
+        assert!(body.contains(
+            r#"name="return" value="/search?q=cubs&amp;start=10""#
+        ));
+
+        let token = FormToken::from_cookie_header(Some(&set_cookie));
+        assert!(body.contains(&format!(r#"value="{}""#, token.value)));
+    }
+
+    #[test]
+    fn test_block_site() {
+        let response = post(
+            "/preferences/block",
+            "site=mlb.com&return=%2Fsearch%3Fq%3Dcubs",
+        );
+
+        assert_eq!(response.status, 303);
+        assert_eq!(response.header("Location"), Some("/search?q=cubs"));
+
+        let cookie = response.header("Set-Cookie").unwrap();
+        assert!(cookie.starts_with("google2005_prefs=blocked=mlb.com&"));
+
+        let response = Router::new(&ResultsPage).handle(
+            &HttpRequest::new("GET", "/search?q=cubs&start=10")
+                .with_header("Cookie", cookie.split(';').next().unwrap()),
+        );
+        assert_eq!(response.status, 404);
+    }
+
+    #[test]
+    fn test_block_site_only_returns_to_own_pages() {
+        let response =
+            post("/preferences/block", "site=mlb.com&return=//evil.example");
+
+        assert_eq!(response.header("Location"), Some("/preferences"));
+        assert_eq!(post("/preferences/block", "site=nope").status, 400);
+    }
+
+    #[test]
+    fn test_own_page() {
+        assert_eq!(
+            Router::own_page("/search?q=cubs&start=10").as_deref(),
+            Some("/search?q=cubs&start=10")
+        );
+        assert_eq!(Router::own_page("search").as_deref(), Some("/search"));
+        assert_eq!(Router::own_page("//evil.example/"), None);
+        assert_eq!(Router::own_page("/\\evil.example/"), None);
+        assert_eq!(Router::own_page("\\\\evil.example/"), None);
+        assert_eq!(Router::own_page("https://evil.example/"), None);
+        assert_eq!(Router::own_page("javascript:alert(1)"), None);
+    }
+
+    #[test]
+    fn test_block_from_older_search_page() {
+        let router = Router::new(&ResultsPage);
+        let search = HttpRequest::new("GET", "/search?q=cubs&start=10");
+
+        let first = router.handle(&search);
+        let set_cookie = first.header("Set-Cookie").unwrap();
+        let cookie = set_cookie.split(';').next().unwrap().to_string();
+        let token = FormToken::from_cookie_header(Some(&cookie));
+        assert!(String::from_utf8(first.body)
+            .unwrap()
+            .contains(&format!(r#"value="{}""#, token.value)));
+
+        // e.g. in another tab
+        let second =
+            router.handle(&search.clone().with_header("Cookie", &cookie));
+        assert_eq!(second.header("Set-Cookie"), None);
+
+        let response = router.handle(
+            &HttpRequest::new("POST", "/preferences/block")
+                .with_header("Cookie", &cookie)
+                .with_body(format!("site=mlb.com&token={}", token.value)),
+        );
+        assert_eq!(response.status, 303);
+    }
+
+    #[test]
+    fn test_block_site_only_from_own_pages() {
+        let response = get("/preferences/block?site=mlb.com");
+
+        assert_eq!(response.status, 405);
+        assert_eq!(response.header("Allow"), Some("POST"));
+        assert_eq!(response.header("Set-Cookie"), None);
+
+        let posts = [
+            HttpRequest::new("POST", "/preferences/block")
+                .with_body("site=mlb.com&token=0123abcd"),
+            HttpRequest::new("POST", "/preferences/block")
+                .with_header("Cookie", TOKEN_COOKIE)
+                .with_body("site=mlb.com&token=4567ef01"),
+        ];
+        for post in &posts {
+            let response = Router::new(&ResultsPage).handle(post);
+
+            assert_eq!(response.status, 403);
+            assert_eq!(response.header("Set-Cookie"), None);
+        }
+    }
+
+    #[test]
+    fn test_preferences_page() {
+        let response = Router::new(&ResultsPage).handle(
+            &HttpRequest::new("GET", "/preferences").with_header(
+                "Cookie",
+                "google2005_prefs=blocked=pinterest.com+quora.com",
+            ),
+        );
+        let body = String::from_utf8(response.body).unwrap();
+
+        assert_eq!(response.status, 200);
+        assert!(body.contains("pinterest.com\nquora.com</textarea>"));
+        assert!(body.contains(r#"method="POST""#));
+    }
+
+    #[test]
+    fn test_save_preferences() {
+        let response = post("/preferences", "blocked=&pinned=mlb.com");
+
+        assert_eq!(response.status, 303);
+        assert!(response
+            .header("Set-Cookie")
+            .unwrap()
+            .starts_with("google2005_prefs=blocked=&pinned=mlb.com;"));
+
+        // Reading the page never changes the preferences
+        let response = get("/preferences?blocked=&pinned=mlb.com");
+
+        assert_eq!(response.status, 200);
+        assert!(!response
+            .header("Set-Cookie")
+            .unwrap()
+            .starts_with("google2005_prefs="));
+    }
+
+    #[test]
+    fn test_api_search_reports_errors() {
+        let response = Router::new(&RateLimited)
+            .handle(&HttpRequest::new("GET", "/api/search?q=cubs"));
+
+        assert_eq!(response.status, 502);
+        assert_eq!(response.header("Content-Type"), Some(JSON_CONTENT_TYPE));
+
+        let body = String::from_utf8(response.body).unwrap();
+        assert!(body.contains(r#""code":"upstream_status""#));
+    }
+
+    #[test]
+    fn test_api_search_rejects_bad_params() {
+        let response = get("/api/search?q=cubs&num=lots");
+
+        assert_eq!(response.status, 400);
+        assert_eq!(response.header("Content-Type"), Some(JSON_CONTENT_TYPE));
+
+        let body = String::from_utf8(response.body).unwrap();
+        assert!(body.contains(r#""code":"bad_query""#));
+    }
+
+    #[test]
+    fn test_replays_fixtures() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+        let fixtures = ReplayFetcher::new(dir);
+        let router = Router::new(&fixtures);
+
+        let response =
+            router.handle(&HttpRequest::new("GET", "/search?q=chicago+cubs"));
+
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            response.header(HEALTH_HEADER),
+            Some("ok; fingerprint=4127170f; kept=10")
+        );
+
+        let body = String::from_utf8(response.body).unwrap();
+        assert!(body.contains("https://en.wikipedia.org/wiki/Chicago_Cubs"));
+
+        let response = router
+            .handle(&HttpRequest::new("GET", "/api/search?q=cubs&start=10"));
+
+        assert_eq!(response.status, 503);
+    }
+}
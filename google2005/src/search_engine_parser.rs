@@ -0,0 +1,349 @@
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::Serialize;
+use url::Url;
+
+use crate::health_report::MarkupStats;
+use crate::parser;
+use crate::search_result::SearchResult;
+use crate::utils::google2005_error::Google2005Error;
+
+/// Parsers tried, in order, when `scrape` is asked to detect the layout.
+pub const DEFAULT_PARSERS: &[&dyn SearchEngineParser] = &[&GoogleParser];
+
+/// Turns a search engine's results page into `SearchResult`s.
+///
+/// Implement this to support another engine, or another Google layout,
+/// and hand it to `google2005::scrape_with`.
+pub trait SearchEngineParser {
+    /// Short, stable name used in logs and in `PageMetadata`.
+    fn name(&self) -> &'static str;
+
+    /// Whether `dom` looks like a page this parser understands.
+    fn detect(&self, dom: &Html) -> bool;
+
+    fn parse<'a>(
+        &self,
+        dom: &'a Html,
+    ) -> Result<ParsedPage<'a>, Google2005Error>;
+}
+
+#[derive(Debug)]
+pub struct ParsedPage<'a> {
+    pub results: Vec<SearchResult<'a>>,
+    pub metadata: PageMetadata,
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct PageMetadata {
+    pub parser: &'static str,
+    pub markup: MarkupStats,
+    /// The query from the page's "Did you mean" line, if it had one.
+    pub spelling_suggestion: Option<String>,
+}
+
+/// How `scrape_with` picks the parser for a page.
+#[derive(Clone, Copy)]
+pub enum ParserChoice<'p> {
+    /// Use the first parser whose `detect` accepts the page, falling back to
+    /// `GoogleParser` when none do.
+    Detect(&'p [&'p dyn SearchEngineParser]),
+    Explicit(&'p dyn SearchEngineParser),
+}
+
+impl<'p> ParserChoice<'p> {
+    pub fn select(self, dom: &Html) -> &'p dyn SearchEngineParser {
+        match self {
+            ParserChoice::Explicit(parser) => parser,
+            ParserChoice::Detect(parsers) => parsers
+                .iter()
+                .find(|parser| parser.detect(dom))
+                .copied()
+                .unwrap_or(&GoogleParser),
+        }
+    }
+}
+
+impl Default for ParserChoice<'_> {
+    fn default() -> Self {
+        ParserChoice::Detect(DEFAULT_PARSERS)
+    }
+}
+
+/// The original heading-and-span walker over Google's results markup.
+pub struct GoogleParser;
+
+// Fingerprints (see `MarkupStats`) of the results markup `parse` is known to
+// handle, taken from real pages rather than hand-written fixtures. Add a
+// recorded page's fingerprint once its results parse correctly; the golden
+// test fails on recordings whose fingerprint is missing.
+const GOOGLE_FINGERPRINTS: &[&str] = &[
+    // no-javascript layout: `div.ZINbbc > div.egMi0 > a > h3`, as in the
+    // results the parser tests were copied from
+    "4127170f",
+];
+
+// The text Google puts before a link to the corrected query.
+const SPELLING_PROMPTS: &[&str] = &["Did you mean", "Showing results for"];
+
+impl GoogleParser {
+    // Google answers suspected bots with a captcha form posting to /sorry/
+    // instead of a results page.
+    fn is_captcha_page(dom: &Html) -> bool {
+        let captcha =
+            Selector::parse(r#"#captcha-form, form[action*="/sorry"]"#)
+                .unwrap();
+
+        dom.select(&captcha).next().is_some()
+    }
+
+    // Google links the corrected query with `spell=1`, right after "Did
+    // you mean:". Older pages only have the prompt.
+    fn spelling_suggestion(dom: &Html) -> Option<String> {
+        let links = Selector::parse("a[href]").unwrap();
+
+        dom.select(&links)
+            .filter(|link| Self::is_spelling_link(link))
+            .find_map(|link| Self::searched_for(link.value().attr("href")?))
+    }
+
+    fn is_spelling_link(link: &ElementRef) -> bool {
+        let href = link.value().attr("href").unwrap_or_default();
+        if href.contains("spell=1") {
+            return true;
+        }
+
+        let prompt: String = link
+            .prev_siblings()
+            .flat_map(|sibling| match sibling.value() {
+                Node::Text(text) => vec![text.to_string()],
+                Node::Element(_) => ElementRef::wrap(sibling)
+                    .map(|element| element.text().map(String::from).collect())
+                    .unwrap_or_default(),
+                _ => vec![],
+            })
+            .collect();
+
+        SPELLING_PROMPTS.iter().any(|start| prompt.contains(start))
+    }
+
+    // The `q` of a link to a Google search.
+    fn searched_for(href: &str) -> Option<String> {
+        let google = Url::parse("https://www.google.com/").unwrap();
+        let url = google.join(href).ok()?;
+        if url.path() != "/search" {
+            return None;
+        }
+
+        url.query_pairs()
+            .find(|(key, _)| key == "q")
+            .map(|(_, query)| query.trim().to_string())
+            .filter(|query| !query.is_empty())
+    }
+}
+
+impl SearchEngineParser for GoogleParser {
+    fn name(&self) -> &'static str {
+        "google"
+    }
+
+    fn detect(&self, dom: &Html) -> bool {
+        let title = Selector::parse("title").unwrap();
+        let search_form = Selector::parse(r#"form[action="/search"]"#).unwrap();
+
+        let titled_google = dom
+            .select(&title)
+            .next()
+            .map(|title| title.text().collect::<String>())
+            .is_some_and(|title| title.ends_with("Google Search"));
+
+        titled_google || dom.select(&search_form).next().is_some()
+    }
+
+    fn parse<'a>(
+        &self,
+        dom: &'a Html,
+    ) -> Result<ParsedPage<'a>, Google2005Error> {
+        if Self::is_captcha_page(dom) {
+            return Err(Google2005Error::Blocked);
+        }
+
+        let results = match parser::parse(dom) {
+            Some(results) => results,
+            None => {
+                return Err(Google2005Error::parse_failed(
+                    "results page has no body",
+                ))
+            }
+        };
+
+        let mut markup = MarkupStats::of(dom);
+        markup.unfamiliar =
+            !GOOGLE_FINGERPRINTS.contains(&markup.fingerprint.as_str());
+
+        Ok(ParsedPage {
+            results,
+            metadata: PageMetadata {
+                parser: self.name(),
+                markup,
+                spelling_suggestion: Self::spelling_suggestion(dom),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct NeverParser;
+
+    impl SearchEngineParser for NeverParser {
+        fn name(&self) -> &'static str {
+            "never"
+        }
+
+        fn detect(&self, _dom: &Html) -> bool {
+            false
+        }
+
+        fn parse<'a>(
+            &self,
+            _dom: &'a Html,
+        ) -> Result<ParsedPage<'a>, Google2005Error> {
+            Ok(ParsedPage {
+                results: vec![],
+                metadata: PageMetadata {
+                    parser: self.name(),
+                    ..PageMetadata::default()
+                },
+            })
+        }
+    }
+
+    #[test]
+    fn test_google_parser_detects_results_page() {
+        let dom = Html::parse_document(
+            "<html><head><title>cubs - Google Search</title></head></html>",
+        );
+
+        assert!(GoogleParser.detect(&dom));
+    }
+
+    #[test]
+    fn test_google_parser_detects_search_form() {
+        let dom = Html::parse_document(
+            r#"<body><form action="/search"><input name="q"></form></body>"#,
+        );
+
+        assert!(GoogleParser.detect(&dom));
+    }
+
+    #[test]
+    fn test_detect_falls_back_to_google() {
+        let dom = Html::parse_document("<p>not a results page</p>");
+        let parsers: &[&dyn SearchEngineParser] = &[&NeverParser];
+
+        assert_eq!(ParserChoice::Detect(parsers).select(&dom).name(), "google");
+    }
+
+    #[test]
+    fn test_explicit_choice_skips_detection() {
+        let dom = Html::parse_document(
+            "<html><head><title>cubs - Google Search</title></head></html>",
+        );
+
+        assert_eq!(
+            ParserChoice::Explicit(&NeverParser).select(&dom).name(),
+            "never"
+        );
+    }
+
+    #[test]
+    fn test_google_parser_reports_metadata() {
+        let dom = Html::parse_document(
+            r#"<body><a href="https://www.mlb.com/cubs"><h3>Cubs</h3></a></body>"#,
+        );
+
+        let page = GoogleParser.parse(&dom).unwrap();
+
+        assert_eq!(page.results.len(), 1);
+        assert_eq!(page.metadata.parser, "google");
+    }
+
+    #[test]
+    fn test_google_parser_knows_no_javascript_layout() {
+        // A result as Google served it, trimmed as in the parser tests
+        let result = concat!(
+            r#"<div class="ZINbbc luh4tb xpd O9g5cc uUPGi">"#,
+            r#"<div class="egMi0 kCrYT">"#,
+            r#"<a href="/url?q=https://www.foxsports.com/nfl/david-blough">"#,
+            r#"<h3 class="zBAuLc l97dzf">"#,
+            r#"<div class="BNeawe vvjwJb AP7Wnd">David Blough</div>"#,
+            "</h3></a></div>",
+            r#"<div class="kCrYT"><div class="BNeawe s3v9rd AP7Wnd">"#,
+            "Remains No. 3 QB</div></div></div>"
+        );
+        let dom = Html::parse_document(&format!("<body>{}</body>", result));
+
+        let page = GoogleParser.parse(&dom).unwrap();
+
+        assert!(!page.metadata.markup.unfamiliar);
+    }
+
+    #[test]
+    fn test_google_parser_reads_spelling_suggestion() {
+        let dom = Html::parse_document(concat!(
+            "<body><div><span>Did you mean: </span>",
+            r#"<a href="/search?q=chicago+cubs&amp;spell=1&amp;sa=X">"#,
+            "<b><i>chicago</i></b> cubs</a></div>",
+            r#"<a href="https://www.mlb.com/cubs"><h3>Cubs</h3></a>"#,
+            "</body>"
+        ));
+
+        let page = GoogleParser.parse(&dom).unwrap();
+
+        assert_eq!(
+            page.metadata.spelling_suggestion.as_deref(),
+            Some("chicago cubs")
+        );
+    }
+
+    #[test]
+    fn test_spelling_suggestion_follows_prompt() {
+        let dom = Html::parse_document(concat!(
+            r#"<body><a href="/search?q=cubs+tickets">Cubs tickets</a>"#,
+            "<p>Did you mean: ",
+            r#"<a href="/search?q=chicago+cubs">chicago cubs</a></p>"#,
+            "</body>"
+        ));
+
+        assert_eq!(
+            GoogleParser::spelling_suggestion(&dom).as_deref(),
+            Some("chicago cubs")
+        );
+    }
+
+    #[test]
+    fn test_no_spelling_suggestion() {
+        let dom = Html::parse_document(concat!(
+            r#"<body><a href="/search?q=cubs+tickets">Cubs tickets</a>"#,
+            r#"<a href="https://www.mlb.com/cubs"><h3>Cubs</h3></a>"#,
+            "</body>"
+        ));
+
+        assert_eq!(GoogleParser::spelling_suggestion(&dom), None);
+    }
+
+    #[test]
+    fn test_google_parser_reports_captcha_page() {
+        let dom = Html::parse_document(concat!(
+            "<body><p>Our systems have detected unusual traffic</p>",
+            r#"<form id="captcha-form" action="index"></form></body>"#
+        ));
+
+        assert!(matches!(
+            GoogleParser.parse(&dom),
+            Err(Google2005Error::Blocked)
+        ));
+    }
+}
@@ -0,0 +1,615 @@
+use std::fmt;
+
+use serde::Serialize;
+use url::Url;
+
+use crate::{search_result::SearchResult, url_cleaner::is_on_domain};
+
+// Operators written `name:value`, e.g. `site:mlb.com`.
+const OPERATORS: &[&str] =
+    &["site", "filetype", "ext", "intitle", "before", "after"];
+
+/// One part of a query, as Google reads it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryTerm {
+    Word(String),
+    /// `"chicago cubs"`: the words together, in order.
+    Phrase(String),
+    /// `-term`: leave out results with the term.
+    Exclude(Box<QueryTerm>),
+    /// `site:mlb.com`: only results from the site, subdomains included.
+    Site(String),
+    /// `filetype:pdf`, or `ext:pdf`.
+    FileType(String),
+    /// `intitle:cubs`: only results with the word or phrase in the title.
+    InTitle(String),
+    /// `cubs OR sox`: results with any of the terms.
+    AnyOf(Vec<QueryTerm>),
+    /// `before:2005-12-31`
+    Before(String),
+    /// `after:2005-01-01`
+    After(String),
+}
+
+/// A piece of a result's title or snippet, bolded when it matches the query.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct TextSpan {
+    pub text: String,
+    pub highlighted: bool,
+}
+
+/// A query split into its words, phrases and operators.
+///
+/// The query goes to Google as it was typed, so every operator Google
+/// honours still applies. Google treats exclusions, `site:` and
+/// `filetype:` loosely though, so the results it sends back are checked
+/// against them again (see `contradicted_by`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    pub terms: Vec<QueryTerm>,
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> SearchQuery {
+        let mut terms: Vec<QueryTerm> = vec![];
+        let mut or_pending = false;
+
+        for token in tokens(query) {
+            if (token == "OR" || token == "|") && !terms.is_empty() {
+                or_pending = true;
+                continue;
+            }
+
+            let term = match QueryTerm::parse(token) {
+                Some(term) => term,
+                None => continue,
+            };
+
+            match terms.pop() {
+                Some(previous) if or_pending => {
+                    terms.push(previous.or(term));
+                }
+                Some(previous) => {
+                    terms.push(previous);
+                    terms.push(term);
+                }
+                None => terms.push(term),
+            }
+            or_pending = false;
+        }
+
+        if or_pending {
+            terms.push(QueryTerm::Word("OR".to_string()));
+        }
+
+        SearchQuery { terms }
+    }
+
+    /// Whether the query is more than plain words.
+    pub fn has_operators(&self) -> bool {
+        self.terms
+            .iter()
+            .any(|term| !matches!(term, QueryTerm::Word(_)))
+    }
+
+    /// Whether `result` plainly goes against the query, e.g. it's on a
+    /// site the query excludes. Terms a result's snippet can't prove
+    /// missing, like words, are left to Google.
+    pub fn contradicted_by(&self, result: &SearchResult) -> bool {
+        self.terms.iter().any(|term| term.contradicted_by(result))
+    }
+
+    /// Splits `text` into spans, highlighting the query's words and phrases
+    /// wherever they appear in it, ignoring case and punctuation.
+    pub fn highlight(&self, text: &str) -> Vec<TextSpan> {
+        let wanted = self
+            .terms
+            .iter()
+            .flat_map(QueryTerm::highlighted_words)
+            .filter(|wanted| !wanted.is_empty())
+            .collect::<Vec<_>>();
+        let words = word_ranges(text);
+
+        let mut matches = vec![];
+        for wanted in &wanted {
+            for window in words.windows(wanted.len()) {
+                if window.iter().map(|(_, _, word)| word).eq(wanted) {
+                    let (first, last) = (&window[0], &window[wanted.len() - 1]);
+                    matches.push((first.0, last.1));
+                }
+            }
+        }
+        matches.sort_unstable();
+
+        let mut spans = vec![];
+        let mut at = 0;
+        for (start, end) in matches {
+            if end <= at {
+                continue;
+            }
+            if start > at {
+                spans.push(TextSpan::new(&text[at..start], false));
+            }
+            // a match overlapping the previous one extends its span
+            match spans.last_mut() {
+                Some(span) if span.highlighted && start < at => {
+                    span.text.push_str(&text[at..end])
+                }
+                _ => spans.push(TextSpan::new(&text[start..end], true)),
+            }
+            at = end;
+        }
+        if at < text.len() {
+            spans.push(TextSpan::new(&text[at..], false));
+        }
+
+        spans
+    }
+
+    /// How the query was read, for people, e.g. `cubs, not pinterest,
+    /// only from mlb.com`.
+    pub fn describe(&self) -> String {
+        self.terms
+            .iter()
+            .map(QueryTerm::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl QueryTerm {
+    fn parse(token: &str) -> Option<QueryTerm> {
+        if token.is_empty() {
+            return None;
+        }
+
+        if let Some(excluded) = token.strip_prefix('-') {
+            return match QueryTerm::parse(excluded)? {
+                QueryTerm::Exclude(term) => Some(*term),
+                term => Some(QueryTerm::Exclude(Box::new(term))),
+            };
+        }
+
+        if let Some((name, value)) = token.split_once(':') {
+            let name = name.to_ascii_lowercase();
+            let value = unquote(value);
+
+            if OPERATORS.contains(&name.as_str()) {
+                if let Some(term) = QueryTerm::operator(&name, value) {
+                    return Some(term);
+                }
+            }
+        }
+
+        if token.starts_with('"') {
+            let phrase = unquote(token);
+
+            return if phrase.is_empty() {
+                None
+            } else if phrase.contains(char::is_whitespace) {
+                Some(QueryTerm::Phrase(phrase.to_string()))
+            } else {
+                Some(QueryTerm::Word(phrase.to_string()))
+            };
+        }
+
+        Some(QueryTerm::Word(token.to_string()))
+    }
+
+    // `None` when there's nothing after the colon, e.g. `site:`
+    fn operator(name: &str, value: &str) -> Option<QueryTerm> {
+        let term = match name {
+            "site" => {
+                let site = value.to_ascii_lowercase();
+                let site = site.split_once("://").map_or(&*site, |(_, s)| s);
+                let host = site.split('/').next().unwrap_or_default();
+                let host = host.trim_start_matches('.');
+
+                QueryTerm::Site(strip_www(host).to_string())
+            }
+            "filetype" | "ext" => QueryTerm::FileType(
+                value.trim_start_matches('.').to_ascii_lowercase(),
+            ),
+            "intitle" => QueryTerm::InTitle(value.to_string()),
+            "before" => QueryTerm::Before(value.to_string()),
+            _ => QueryTerm::After(value.to_string()),
+        };
+
+        match &term {
+            QueryTerm::Site(text)
+            | QueryTerm::FileType(text)
+            | QueryTerm::InTitle(text)
+            | QueryTerm::Before(text)
+            | QueryTerm::After(text)
+                if text.is_empty() =>
+            {
+                None
+            }
+            _ => Some(term),
+        }
+    }
+
+    // The words to bold in results, each group together and in order.
+    fn highlighted_words(&self) -> Vec<Vec<String>> {
+        match self {
+            QueryTerm::Word(text)
+            | QueryTerm::Phrase(text)
+            | QueryTerm::InTitle(text) => vec![words(text)],
+            QueryTerm::AnyOf(terms) => terms
+                .iter()
+                .flat_map(QueryTerm::highlighted_words)
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    fn or(self, other: QueryTerm) -> QueryTerm {
+        match self {
+            QueryTerm::AnyOf(mut terms) => {
+                terms.push(other);
+                QueryTerm::AnyOf(terms)
+            }
+            term => QueryTerm::AnyOf(vec![term, other]),
+        }
+    }
+
+    // Only terms a result can be checked against by its URL are
+    // contradicted by leaving them out.
+    fn contradicted_by(&self, result: &SearchResult) -> bool {
+        match self {
+            QueryTerm::Exclude(term) => term.shown_in(result),
+            QueryTerm::Site(_) | QueryTerm::FileType(_) => {
+                !self.shown_in(result)
+            }
+            QueryTerm::AnyOf(terms) => {
+                terms.iter().all(|term| term.contradicted_by(result))
+            }
+            _ => false,
+        }
+    }
+
+    // Whether the result plainly has the term, in its URL, title or
+    // snippet.
+    fn shown_in(&self, result: &SearchResult) -> bool {
+        match self {
+            QueryTerm::Word(text) | QueryTerm::Phrase(text) => {
+                let wanted = words(text);
+
+                [url_text(result), title(result), description(result)]
+                    .iter()
+                    .any(|text| contains_words(&words(text), &wanted))
+            }
+            QueryTerm::InTitle(text) => {
+                contains_words(&words(&title(result)), &words(text))
+            }
+            QueryTerm::Site(site) => host(result)
+                .is_some_and(|host| is_on_domain(strip_www(&host), site)),
+            QueryTerm::FileType(extension) => Url::parse(&result.url)
+                .is_ok_and(|url| {
+                    url.path()
+                        .to_ascii_lowercase()
+                        .ends_with(&format!(".{}", extension))
+                }),
+            QueryTerm::AnyOf(terms) => {
+                terms.iter().any(|term| term.shown_in(result))
+            }
+            QueryTerm::Exclude(_)
+            | QueryTerm::Before(_)
+            | QueryTerm::After(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for QueryTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryTerm::Word(word) => write!(f, "{}", word),
+            QueryTerm::Phrase(phrase) => write!(f, "\"{}\"", phrase),
+            QueryTerm::Exclude(term) => write!(f, "not {}", term),
+            QueryTerm::Site(site) => write!(f, "only from {}", site),
+            QueryTerm::FileType(extension) => {
+                write!(f, "only .{} files", extension)
+            }
+            QueryTerm::InTitle(text) => write!(f, "{} in the title", text),
+            QueryTerm::AnyOf(terms) => {
+                let terms =
+                    terms.iter().map(QueryTerm::to_string).collect::<Vec<_>>();
+
+                write!(f, "{}", terms.join(" or "))
+            }
+            QueryTerm::Before(date) => write!(f, "before {}", date),
+            QueryTerm::After(date) => write!(f, "after {}", date),
+        }
+    }
+}
+
+impl TextSpan {
+    fn new(text: &str, highlighted: bool) -> TextSpan {
+        TextSpan {
+            text: text.to_string(),
+            highlighted,
+        }
+    }
+}
+
+// Splits `query` on whitespace, except inside double quotes, so
+// `-"chicago cubs"` and `intitle:"cubs win"` stay whole.
+fn tokens(query: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let mut start = None;
+    let mut quoted = false;
+
+    for (i, c) in query.char_indices() {
+        match (start, c) {
+            (_, '"') => {
+                quoted = !quoted;
+                start.get_or_insert(i);
+            }
+            (Some(token_start), c) if c.is_whitespace() && !quoted => {
+                tokens.push(&query[token_start..i]);
+                start = None;
+            }
+            (None, c) if !c.is_whitespace() => start = Some(i),
+            _ => {}
+        }
+    }
+
+    if let Some(token_start) = start {
+        tokens.push(&query[token_start..]);
+    }
+
+    tokens
+}
+
+fn strip_www(host: &str) -> &str {
+    host.strip_prefix("www.").unwrap_or(host)
+}
+
+fn unquote(text: &str) -> &str {
+    let text = text.strip_prefix('"').unwrap_or(text);
+
+    text.strip_suffix('"').unwrap_or(text).trim()
+}
+
+// The lowercased words in `text`, ignoring punctuation.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// Where each of `words(text)` starts and ends in `text`, with the word.
+fn word_ranges(text: &str) -> Vec<(usize, usize, String)> {
+    let mut ranges = vec![];
+    let mut start = None;
+    let ends = text.char_indices().chain([(text.len(), ' ')]);
+
+    for (i, c) in ends {
+        match (start, c.is_alphanumeric()) {
+            (None, true) => start = Some(i),
+            (Some(word_start), false) => {
+                let word = text[word_start..i].to_lowercase();
+                ranges.push((word_start, i, word));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    ranges
+}
+
+fn contains_words(text: &[String], wanted: &[String]) -> bool {
+    !wanted.is_empty()
+        && text.windows(wanted.len()).any(|window| window == wanted)
+}
+
+fn host(result: &SearchResult) -> Option<String> {
+    Url::parse(&result.url)
+        .ok()?
+        .host_str()
+        .map(str::to_ascii_lowercase)
+}
+
+fn url_text(result: &SearchResult) -> String {
+    result.url.to_string()
+}
+
+fn title(result: &SearchResult) -> String {
+    result.title.as_deref().unwrap_or_default().join(" ")
+}
+
+fn description(result: &SearchResult) -> String {
+    result.joined_and_decoded_description()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn result(
+        url: &str,
+        title: &str,
+        description: &str,
+    ) -> SearchResult<'static> {
+        let mut result = SearchResult::new(url.to_string());
+        result.set_title(vec![title.to_string()]);
+        result.add_to_description(vec![description.to_string()]);
+
+        result
+    }
+
+    fn exclude(term: QueryTerm) -> QueryTerm {
+        QueryTerm::Exclude(Box::new(term))
+    }
+
+    #[test]
+    fn test_parses_operators() {
+        let query = SearchQuery::parse(concat!(
+            r#"cubs "wrigley field" -pinterest -"fan shop" site:www.MLB.com "#,
+            r#"filetype:.PDF intitle:"box score" before:2005-12-31 after:2005"#
+        ));
+
+        assert_eq!(
+            query.terms,
+            vec![
+                QueryTerm::Word("cubs".into()),
+                QueryTerm::Phrase("wrigley field".into()),
+                exclude(QueryTerm::Word("pinterest".into())),
+                exclude(QueryTerm::Phrase("fan shop".into())),
+                QueryTerm::Site("mlb.com".into()),
+                QueryTerm::FileType("pdf".into()),
+                QueryTerm::InTitle("box score".into()),
+                QueryTerm::Before("2005-12-31".into()),
+                QueryTerm::After("2005".into()),
+            ]
+        );
+        assert!(query.has_operators());
+    }
+
+    #[test]
+    fn test_parses_or() {
+        let query = SearchQuery::parse("cubs OR sox | white sox OR");
+
+        assert_eq!(
+            query.terms,
+            vec![
+                QueryTerm::AnyOf(vec![
+                    QueryTerm::Word("cubs".into()),
+                    QueryTerm::Word("sox".into()),
+                    QueryTerm::Word("white".into()),
+                ]),
+                QueryTerm::Word("sox".into()),
+                QueryTerm::Word("OR".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plain_words() {
+        let query = SearchQuery::parse(r#"  chicago  cubs: "" site: -"#);
+
+        assert_eq!(
+            query.terms,
+            vec![
+                QueryTerm::Word("chicago".into()),
+                QueryTerm::Word("cubs:".into()),
+                QueryTerm::Word("site:".into()),
+            ]
+        );
+        assert!(!query.has_operators());
+    }
+
+    #[test]
+    fn test_describe() {
+        let query = SearchQuery::parse(
+            "cubs OR sox -pinterest site:mlb.com intitle:scores",
+        );
+
+        assert_eq!(
+            query.describe(),
+            "cubs or sox, not pinterest, only from mlb.com, scores in the title"
+        );
+    }
+
+    fn highlighted(query: &str, text: &str) -> Vec<(String, bool)> {
+        SearchQuery::parse(query)
+            .highlight(text)
+            .into_iter()
+            .map(|span| (span.text, span.highlighted))
+            .collect()
+    }
+
+    #[test]
+    fn test_highlight() {
+        assert_eq!(
+            highlighted(
+                r#"cubs "wrigley field" -tickets site:mlb.com"#,
+                "Chicago Cubs: tickets for Wrigley  Field, home of the CUBS"
+            ),
+            vec![
+                ("Chicago ".into(), false),
+                ("Cubs".into(), true),
+                (": tickets for ".into(), false),
+                ("Wrigley  Field".into(), true),
+                (", home of the ".into(), false),
+                ("CUBS".into(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_highlight_overlapping_terms() {
+        assert_eq!(
+            highlighted(
+                r#""chicago cubs" "cubs win" OR sox"#,
+                "Chicago Cubs win!"
+            ),
+            vec![("Chicago Cubs win".into(), true), ("!".into(), false)]
+        );
+        assert_eq!(
+            highlighted("cubs", "Wrigley Field"),
+            vec![("Wrigley Field".into(), false)]
+        );
+        assert!(highlighted("cubs", "").is_empty());
+    }
+
+    #[test]
+    fn test_contradicted_by_exclusions() {
+        let query = SearchQuery::parse(r#"cubs -pinterest -"fan shop""#);
+
+        assert!(query.contradicted_by(&result(
+            "https://www.pinterest.com/cubs",
+            "Cubs pins",
+            "",
+        )));
+        assert!(query.contradicted_by(&result(
+            "https://www.mlb.com/cubs/shop",
+            "Cubs Team Store",
+            "The official Cubs fan shop.",
+        )));
+        assert!(!query.contradicted_by(&result(
+            "https://www.mlb.com/cubs",
+            "Chicago Cubs",
+            "Shop for fans, and pinterested readers",
+        )));
+    }
+
+    #[test]
+    fn test_contradicted_by_site_and_filetype() {
+        let query = SearchQuery::parse("cubs site:mlb.com filetype:pdf");
+
+        assert!(!query.contradicted_by(&result(
+            "https://www.mlb.com/cubs/schedule.PDF",
+            "Schedule",
+            "",
+        )));
+        assert!(query.contradicted_by(&result(
+            "https://www.mlb.com/cubs/schedule",
+            "Schedule",
+            "",
+        )));
+        assert!(query.contradicted_by(&result(
+            "https://notmlb.com/cubs.pdf",
+            "Schedule",
+            "",
+        )));
+    }
+
+    #[test]
+    fn test_contradicted_by_any_of() {
+        let query = SearchQuery::parse("cubs site:mlb.com OR site:espn.com");
+
+        assert!(!query.contradicted_by(&result(
+            "https://www.espn.com/mlb/team/_/name/chc",
+            "Cubs",
+            "",
+        )));
+        assert!(query.contradicted_by(&result(
+            "https://www.cbssports.com/mlb/teams/CHC",
+            "Cubs",
+            "",
+        )));
+    }
+}
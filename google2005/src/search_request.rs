@@ -1,42 +1,383 @@
-use urlencoding::decode;
+use std::ops::RangeInclusive;
+use url::form_urlencoded;
 
-pub struct SearchRequest<'a> {
-    pub params: &'a str,
+use crate::{
+    backfill::UpstreamCursor, preferences::Preferences,
+    response_format::ResponseFormat, search_query::SearchQuery,
+    utils::google2005_error::Google2005Error,
+};
+
+/// The last `start` Google serves results from; it stops after about the
+/// 1000th.
+pub const MAX_START: u16 = 990;
+
+const STARTS: RangeInclusive<u16> = 0..=MAX_START;
+// `num=0` asks Google for an empty page.
+const NUMS: RangeInclusive<u16> = 1..=100;
+// More links than any results page has.
+const SKIPS: RangeInclusive<u16> = 0..=1000;
+// Google refuses longer queries anyway.
+const MAX_QUERY_LENGTH: usize = 2048;
+
+// The `tbs` part that asks for results matching the query word for word.
+const VERBATIM: &str = "li:1";
+
+/// Google's SafeSearch, `safe`. Left unset, Google decides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SafeSearch {
+    Off,
+    Strict,
+}
+
+impl SafeSearch {
+    pub fn param(&self) -> &'static str {
+        match self {
+            SafeSearch::Off => "off",
+            SafeSearch::Strict => "active",
+        }
+    }
+
+    fn parse(value: &str) -> Result<SafeSearch, Google2005Error> {
+        match value {
+            "off" => Ok(SafeSearch::Off),
+            "active" | "strict" => Ok(SafeSearch::Strict),
+            _ => Err(Google2005Error::bad_query("safe must be off or active")),
+        }
+    }
+}
+
+/// How recent results must be, the `qdr:` part of `tbs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeRange {
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl TimeRange {
+    pub const ALL: [TimeRange; 5] = [
+        TimeRange::Hour,
+        TimeRange::Day,
+        TimeRange::Week,
+        TimeRange::Month,
+        TimeRange::Year,
+    ];
+
+    pub fn param(&self) -> &'static str {
+        match self {
+            TimeRange::Hour => "qdr:h",
+            TimeRange::Day => "qdr:d",
+            TimeRange::Week => "qdr:w",
+            TimeRange::Month => "qdr:m",
+            TimeRange::Year => "qdr:y",
+        }
+    }
+
+    fn parse(tbs: &str) -> Option<TimeRange> {
+        TimeRange::ALL.into_iter().find(|range| range.param() == tbs)
+    }
+}
+
+/// A search, as read from the query string of a `/search` request.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchRequest {
+    /// `q`, decoded. Sent to Google as it is, operators and all.
     pub search_string: String,
+    /// `search_string` split into its words, phrases and operators.
+    pub query: SearchQuery,
+    /// `start`: how many results to skip.
     pub start: u16,
+    /// `hl`: the interface language.
+    pub hl: Option<String>,
+    /// `gl`: the country results are for.
+    pub gl: Option<String>,
+    /// `lr`: only results in this language, e.g. `lang_fr`.
+    pub lr: Option<String>,
+    /// `num`: results per upstream page.
+    pub num: Option<u16>,
+    /// `filter`: `0` to keep results Google considers similar.
+    pub filter: Option<String>,
+    /// `safe`: SafeSearch.
+    pub safe: Option<SafeSearch>,
+    /// `tbs=qdr:*`: only results from the last hour, day, etc.
+    pub time_range: Option<TimeRange>,
+    /// `tbs=li:1`: results matching the query word for word.
+    pub verbatim: bool,
+    /// The other search tools in `tbs`, e.g. custom date ranges, passed on
+    /// to Google as they are.
+    pub tbs: Vec<String>,
+    /// `debug=1`: explain the filter instead of showing results.
+    pub debug: bool,
+    /// `format`: render the results as a feed. Unknown formats are left
+    /// to the `Accept` header.
+    pub format: Option<ResponseFormat>,
+    /// `ustart` and `uskip`: where in Google's results this page starts,
+    /// set by the link from the page before it.
+    pub upstream: Option<UpstreamCursor>,
+    /// Every other parameter, in order, passed on to Google as it is.
+    pub other: Vec<(String, String)>,
 }
 
-impl<'a> SearchRequest<'a> {
-    pub fn new(params_string: &'a str) -> Self {
-        SearchRequest {
-            params: params_string,
-            search_string: Self::search_string(params_string),
-            start: Self::start(params_string),
+impl SearchRequest {
+    /// Reads a form-urlencoded query string such as `q=cubs&start=10`, in
+    /// any order. Repeated parameters take their last value.
+    pub fn parse(
+        query_string: &str,
+    ) -> Result<SearchRequest, Google2005Error> {
+        let mut request = SearchRequest::default();
+        let mut query = None;
+        let (mut ustart, mut uskip) = (None, None);
+
+        for (key, value) in form_urlencoded::parse(query_string.as_bytes()) {
+            let value = value.into_owned();
+
+            match key.as_ref() {
+                "q" => query = Some(value),
+                "start" => {
+                    request.start = Self::number("start", &value, STARTS)?
+                }
+                "num" => {
+                    request.num = Some(Self::number("num", &value, NUMS)?)
+                }
+                "hl" => request.hl = Some(Self::code("hl", value)?),
+                "gl" => request.gl = Some(Self::code("gl", value)?),
+                "lr" => request.lr = Some(Self::code("lr", value)?),
+                "filter" => request.filter = Some(value),
+                "safe" => request.safe = Some(SafeSearch::parse(&value)?),
+                "tbs" => request.read_tbs(&value),
+                "debug" => request.debug = value == "1",
+                "format" => request.format = ResponseFormat::from_param(&value),
+                "ustart" => {
+                    ustart = Some(Self::number("ustart", &value, STARTS)?)
+                }
+                "uskip" => {
+                    uskip = Some(Self::number("uskip", &value, SKIPS)?)
+                }
+                key => request.other.push((key.to_string(), value)),
+            }
+        }
+
+        let query = query.unwrap_or_default();
+        if query.trim().is_empty() {
+            return Err(Google2005Error::bad_query("Please enter a query"));
+        }
+        if query.len() > MAX_QUERY_LENGTH {
+            return Err(Google2005Error::bad_query("Your query is too long"));
         }
+        request.query = SearchQuery::parse(&query);
+        request.search_string = query;
+
+        if ustart.is_some() || uskip.is_some() {
+            request.upstream = Some(UpstreamCursor {
+                start: ustart.unwrap_or(request.start),
+                skip: uskip.unwrap_or_default(),
+            });
+        }
+
+        Ok(request)
     }
 
-    pub fn search_string(params_string: &'a str) -> String {
-        let q = params_string.split("&").collect::<Vec<&str>>()[0];
+    /// The query string for this search, without our own parameters and
+    /// with `start` replaced by `start`.
+    pub fn to_query_string(&self, start: u16) -> String {
+        let mut serializer = form_urlencoded::Serializer::new(String::new());
+        serializer.append_pair("q", &self.search_string);
 
-        let q = decode(q).unwrap();
-        //replace + with space
-        let q = q.replace("+", " ");
+        let num = self.num.map(|num| num.to_string());
+        let tbs = self.tbs_param();
+        let optional = [
+            ("hl", self.hl.as_deref()),
+            ("gl", self.gl.as_deref()),
+            ("lr", self.lr.as_deref()),
+            ("num", num.as_deref()),
+            ("filter", self.filter.as_deref()),
+            ("safe", self.safe.as_ref().map(SafeSearch::param)),
+            ("tbs", tbs.as_deref()),
+        ];
+        for (key, value) in optional {
+            if let Some(value) = value {
+                serializer.append_pair(key, value);
+            }
+        }
+
+        serializer.extend_pairs(&self.other);
+
+        if start > 0 {
+            serializer.append_pair("start", &start.to_string());
+        }
+
+        serializer.finish()
+    }
+
+    /// Google's results URL for this search, at `start` rather than at
+    /// `self.start`.
+    pub fn upstream_url(&self, start: u16) -> String {
+        format!(
+            "https://www.google.com/search?{}",
+            self.to_query_string(start)
+        )
+    }
 
-        q.to_string()
+    /// Fills in the `hl`, `gl` and `lr` the query string left out: from
+    /// the user's preferences, and failing that, `hl` and `gl` from the
+    /// browser's `Accept-Language` header.
+    pub fn default_locale(
+        &mut self,
+        preferences: &Preferences,
+        accept_language: Option<&str>,
+    ) {
+        let browser = accept_language.and_then(preferred_language);
+        let browser_region = browser.as_deref().and_then(region);
+
+        if self.hl.is_none() {
+            self.hl = preferences.language.clone().or(browser);
+        }
+        if self.gl.is_none() {
+            self.gl = preferences.region.clone().or(browser_region);
+        }
+        if self.lr.is_none() {
+            self.lr = preferences.result_language.clone();
+        }
     }
 
-    pub fn start(params_string: &'a str) -> u16 {
-        let mut start = 0;
+    /// `hl`, `gl`, `lr`, `safe` and `tbs`, each with a leading `&`, for
+    /// links to other pages of this search.
+    pub fn page_params(&self) -> String {
+        let mut params = String::new();
+        let tbs = self.tbs_param();
+        let kept = [
+            ("hl", self.hl.as_deref()),
+            ("gl", self.gl.as_deref()),
+            ("lr", self.lr.as_deref()),
+            ("safe", self.safe.as_ref().map(SafeSearch::param)),
+            ("tbs", tbs.as_deref()),
+        ];
 
-        for param in params_string.split("&") {
-            if param.starts_with("start=") {
-                start = param.split("=").collect::<Vec<&str>>()[1]
-                    .parse::<u16>()
-                    .unwrap();
+        for (key, value) in kept {
+            if let Some(value) = value {
+                params.push('&');
+                params.push_str(
+                    &form_urlencoded::Serializer::new(String::new())
+                        .append_pair(key, value)
+                        .finish(),
+                );
             }
         }
 
-        start
+        params
+    }
+
+    /// The `lang` attribute for pages in this search's language.
+    pub fn html_lang(&self) -> String {
+        self.hl.as_deref().unwrap_or("en").replace('_', "-")
+    }
+
+    fn read_tbs(&mut self, tbs: &str) {
+        self.time_range = None;
+        self.verbatim = false;
+        self.tbs.clear();
+
+        for part in tbs.split(',').filter(|part| !part.is_empty()) {
+            match TimeRange::parse(part) {
+                Some(time_range) => self.time_range = Some(time_range),
+                None if part == VERBATIM => self.verbatim = true,
+                None => self.tbs.push(part.to_string()),
+            }
+        }
+    }
+
+    // `tbs` put back together, or `None` without any search tools
+    fn tbs_param(&self) -> Option<String> {
+        let parts = self
+            .time_range
+            .map(|time_range| time_range.param())
+            .into_iter()
+            .chain(self.verbatim.then_some(VERBATIM))
+            .chain(self.tbs.iter().map(String::as_str))
+            .collect::<Vec<_>>();
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(","))
+        }
+    }
+
+    fn number(
+        name: &str,
+        value: &str,
+        range: RangeInclusive<u16>,
+    ) -> Result<u16, Google2005Error> {
+        match value.parse::<u16>() {
+            Ok(number) if range.contains(&number) => Ok(number),
+            _ => Err(Google2005Error::bad_query(&format!(
+                "{} must be a number from {} to {}",
+                name,
+                range.start(),
+                range.end()
+            ))),
+        }
+    }
+
+    fn code(name: &str, value: String) -> Result<String, Google2005Error> {
+        if is_locale_code(&value) {
+            Ok(value)
+        } else {
+            Err(Google2005Error::bad_query(&format!(
+                "{} must be a language or country code",
+                name
+            )))
+        }
+    }
+}
+
+/// Whether `value` looks like a language or country code, e.g. `en`,
+/// `pt-BR`, `zh_TW` or `lang_fr`.
+pub(crate) fn is_locale_code(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 10
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// The language the browser would like most, e.g. `pt-BR` for
+// `pt-BR,pt;q=0.9,en;q=0.8`.
+fn preferred_language(accept_language: &str) -> Option<String> {
+    let mut preferred: Option<(&str, f32)> = None;
+
+    for range in accept_language.split(',') {
+        let mut parts = range.split(';').map(str::trim);
+        let tag = parts.next().unwrap_or_default();
+        let quality = parts
+            .find_map(|param| param.strip_prefix("q="))
+            .map_or(Some(1.0), |q| q.parse::<f32>().ok());
+
+        let quality = match quality {
+            Some(quality) if quality > 0.0 => quality,
+            _ => continue,
+        };
+        if tag == "*" || !is_locale_code(tag) {
+            continue;
+        }
+        if preferred.is_none_or(|(_, best)| quality > best) {
+            preferred = Some((tag, quality));
+        }
+    }
+
+    preferred.map(|(tag, _)| tag.to_string())
+}
+
+// The country in a language tag, e.g. `br` for `pt-BR`.
+fn region(language: &str) -> Option<String> {
+    let (_, region) = language.split_once(&['-', '_'][..])?;
+
+    if region.len() == 2 && region.chars().all(|c| c.is_ascii_alphabetic()) {
+        Some(region.to_ascii_lowercase())
+    } else {
+        None
     }
 }
 
@@ -44,35 +385,227 @@ impl<'a> SearchRequest<'a> {
 mod test {
     use super::*;
 
+    fn bad_query(query_string: &str) -> String {
+        match SearchRequest::parse(query_string) {
+            Err(e @ Google2005Error::BadQuery(_)) => e.to_string(),
+            other => panic!("parsed {:?}", other),
+        }
+    }
+
     #[test]
     fn test_search_string() {
-        let params = "cubs&start=0";
+        let request = SearchRequest::parse("q=cubs&start=0").unwrap();
 
-        assert_eq!(SearchRequest::search_string(params), "cubs".to_string());
+        assert_eq!(request.search_string, "cubs".to_string());
     }
 
     #[test]
     fn test_search_string_decodes_params() {
-        let params = "george+clooney&start=10";
+        let request = SearchRequest::parse("q=george+clooney%21").unwrap();
 
-        assert_eq!(SearchRequest::search_string(params), "george clooney");
+        assert_eq!(request.search_string, "george clooney!");
     }
 
     #[test]
     fn test_start_defaults_to_zero() {
-        let params = "cubs";
+        let request = SearchRequest::parse("q=cubs").unwrap();
 
-        assert_eq!(SearchRequest::start(params), 0);
+        assert_eq!(request.start, 0);
 
-        let params = "george+clooney&foo=bar";
+        let request =
+            SearchRequest::parse("q=george+clooney&foo=bar").unwrap();
 
-        assert_eq!(SearchRequest::start(params), 0);
+        assert_eq!(request.start, 0);
     }
 
     #[test]
     fn test_start_finds_start() {
-        let params = "george+clooney&start=10";
+        let request =
+            SearchRequest::parse("start=10&q=george+clooney").unwrap();
+
+        assert_eq!(request.start, 10);
+        assert_eq!(request.search_string, "george clooney");
+    }
+
+    #[test]
+    fn test_reads_google_params() {
+        let request = SearchRequest::parse(concat!(
+            "hl=en&q=cubs&gl=us&num=20",
+            "&filter=0&safe=active&tbs=qdr:w&ie=UTF-8"
+        ))
+        .unwrap();
+
+        assert_eq!(request.hl.as_deref(), Some("en"));
+        assert_eq!(request.gl.as_deref(), Some("us"));
+        assert_eq!(request.num, Some(20));
+        assert_eq!(request.filter.as_deref(), Some("0"));
+        assert_eq!(request.safe, Some(SafeSearch::Strict));
+        assert_eq!(request.time_range, Some(TimeRange::Week));
+        assert_eq!(request.other, vec![("ie".into(), "UTF-8".into())]);
+    }
+
+    #[test]
+    fn test_reads_format() {
+        let request = SearchRequest::parse("q=cubs&format=rss").unwrap();
+
+        assert_eq!(request.format, Some(ResponseFormat::Rss));
+        assert!(request.other.is_empty());
+
+        let request = SearchRequest::parse("q=cubs&format=pdf").unwrap();
+        assert_eq!(request.format, None);
+    }
+
+    #[test]
+    fn test_reads_upstream_cursor() {
+        let request =
+            SearchRequest::parse("q=cubs&start=10&ustart=10&uskip=6").unwrap();
+
+        assert_eq!(
+            request.upstream,
+            Some(UpstreamCursor { start: 10, skip: 6 })
+        );
+        assert_eq!(
+            request.upstream_url(10),
+            "https://www.google.com/search?q=cubs&start=10"
+        );
+
+        let request = SearchRequest::parse("q=cubs&start=10").unwrap();
+        assert_eq!(request.upstream, None);
+    }
 
-        assert_eq!(SearchRequest::start(params), 10);
+    #[test]
+    fn test_bad_queries() {
+        assert_eq!(bad_query(""), "Please enter a query");
+        assert_eq!(bad_query("q=+++&start=10"), "Please enter a query");
+        assert_eq!(
+            bad_query("q=cubs&start=abc"),
+            "start must be a number from 0 to 990"
+        );
+        assert_eq!(
+            bad_query("q=cubs&start=99999"),
+            "start must be a number from 0 to 990"
+        );
+        assert_eq!(
+            bad_query("q=cubs&num=0x10"),
+            "num must be a number from 1 to 100"
+        );
+        assert_eq!(
+            bad_query("q=cubs&num=0"),
+            "num must be a number from 1 to 100"
+        );
+        assert_eq!(
+            bad_query("q=cubs&safe=maybe"),
+            "safe must be off or active"
+        );
+        assert_eq!(
+            bad_query("q=cubs&hl=%3Cscript%3E"),
+            "hl must be a language or country code"
+        );
+        assert_eq!(
+            bad_query(&format!("q={}", "a".repeat(3000))),
+            "Your query is too long"
+        );
+    }
+
+    #[test]
+    fn test_survives_bad_encoding() {
+        let request = SearchRequest::parse("q=cubs%ZZ%E2%28").unwrap();
+
+        assert_eq!(request.search_string, "cubs%ZZ\u{fffd}(");
+    }
+
+    #[test]
+    fn test_reads_language_restriction() {
+        let request = SearchRequest::parse("q=cubs&lr=lang_es").unwrap();
+
+        assert_eq!(request.lr.as_deref(), Some("lang_es"));
+        assert_eq!(
+            request.upstream_url(0),
+            "https://www.google.com/search?q=cubs&lr=lang_es"
+        );
+    }
+
+    #[test]
+    fn test_locale_defaults_to_browser_language() {
+        let mut request = SearchRequest::parse("q=cubs").unwrap();
+        request.default_locale(
+            &Preferences::default(),
+            Some("en;q=0.8, pt-BR, pt;q=0.9, *;q=0.5"),
+        );
+
+        assert_eq!(request.hl.as_deref(), Some("pt-BR"));
+        assert_eq!(request.gl.as_deref(), Some("br"));
+        assert_eq!(request.lr, None);
+        assert_eq!(request.html_lang(), "pt-BR");
+        assert_eq!(request.page_params(), "&hl=pt-BR&gl=br");
+    }
+
+    #[test]
+    fn test_locale_prefers_params_then_preferences() {
+        let preferences = Preferences::from_form("hl=de&gl=at&lr=de");
+
+        let mut request = SearchRequest::parse("q=cubs&hl=fr").unwrap();
+        request.default_locale(&preferences, Some("en-US"));
+
+        assert_eq!(request.hl.as_deref(), Some("fr"));
+        assert_eq!(request.gl.as_deref(), Some("at"));
+        assert_eq!(request.lr.as_deref(), Some("lang_de"));
+    }
+
+    #[test]
+    fn test_locale_ignores_bad_accept_language() {
+        let mut request = SearchRequest::parse("q=cubs").unwrap();
+        request.default_locale(
+            &Preferences::default(),
+            Some("<script>, en;q=0, *"),
+        );
+
+        assert_eq!(request.hl, None);
+        assert_eq!(request.gl, None);
+        assert_eq!(request.html_lang(), "en");
+        assert_eq!(request.page_params(), "");
+    }
+
+    #[test]
+    fn test_reads_search_tools() {
+        let request = SearchRequest::parse(
+            "q=cubs&safe=strict&tbs=li:1,cdr:1,qdr:m,cd_min:2005",
+        )
+        .unwrap();
+
+        assert_eq!(request.safe, Some(SafeSearch::Strict));
+        assert_eq!(request.time_range, Some(TimeRange::Month));
+        assert!(request.verbatim);
+        assert_eq!(request.tbs, vec!["cdr:1", "cd_min:2005"]);
+        assert_eq!(
+            request.page_params(),
+            "&safe=active&tbs=qdr%3Am%2Cli%3A1%2Ccdr%3A1%2Ccd_min%3A2005"
+        );
+    }
+
+    #[test]
+    fn test_upstream_url_round_trips() {
+        let request = SearchRequest::parse(
+            "q=caf%C3%A9+%26+bar&format=rss&debug=1&ie=UTF-8&hl=fr&start=10",
+        )
+        .unwrap();
+
+        assert!(request.debug);
+        assert_eq!(
+            request.upstream_url(30),
+            concat!(
+                "https://www.google.com/search",
+                "?q=caf%C3%A9+%26+bar&hl=fr&ie=UTF-8&start=30"
+            )
+        );
+        assert_eq!(
+            SearchRequest::parse(&request.to_query_string(request.start))
+                .unwrap(),
+            SearchRequest {
+                debug: false,
+                format: None,
+                ..request
+            }
+        );
     }
 }
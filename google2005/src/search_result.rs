@@ -1,77 +1,122 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use urlencoding::decode;
 
-#[derive(Debug, Serialize, PartialEq)]
+/// A `SearchResult` that owns its text, for caching or sending across
+/// threads once the `scraper::Html` it was parsed from is gone.
+pub type SearchResultOwned = SearchResult<'static>;
+
+/// A link under a result to another page on the same site.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Sitelink<'a> {
+    pub title: Cow<'a, str>,
+    pub url: Cow<'a, str>,
+}
+
+/// A single hyperlink scraped from a results page.
+///
+/// Parsers borrow every field from the DOM; `into_owned` detaches the
+/// result from it without changing how it is filtered or rendered.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct SearchResult<'a> {
-    pub url: &'a str,
-    pub title: Option<Vec<&'a str>>,
-    pub description: Option<Vec<&'a str>>,
+    pub url: Cow<'a, str>,
+    pub title: Option<Vec<Cow<'a, str>>>,
+    pub description: Option<Vec<Cow<'a, str>>>,
+    /// The green URL line, e.g. `www.mlb.com › cubs`.
+    #[serde(default)]
+    pub display_url: Option<Cow<'a, str>>,
+    /// When the page was published, as Google put it, e.g. `21 hours ago`.
+    #[serde(default)]
+    pub date: Option<Cow<'a, str>>,
+    /// `description` without the date, annotations and separators.
+    #[serde(default)]
+    pub snippet: Option<Cow<'a, str>>,
+    #[serde(default)]
+    pub sitelinks: Vec<Sitelink<'a>>,
+    /// Ratings, prices and the like, e.g. `Rating: 4.5 · 1,234 reviews`.
+    #[serde(default)]
+    pub annotations: Vec<Cow<'a, str>>,
 }
 
 impl<'a> SearchResult<'a> {
-    pub fn new(url: &'a str) -> Self {
+    pub fn new(url: impl Into<Cow<'a, str>>) -> Self {
         SearchResult {
-            url,
+            url: url.into(),
             title: None,
             description: None,
+            display_url: None,
+            date: None,
+            snippet: None,
+            sitelinks: vec![],
+            annotations: vec![],
         }
     }
 
-    pub fn add_to_description(&mut self, description: Vec<&'a str>) {
-        if self.description.is_none() {
-            self.description = Some(description);
-        } else {
-            self.description.as_mut().unwrap().extend(description);
-        }
-    }
-
-    pub fn is_regular_result(&self) -> bool {
-        !self.is_alternative_search()
-            && !self.is_google_logo()
-            && !self.is_image_link()
-            && !self.is_google_ad()
-            && !self.is_google_logistics()
-            && self.has_title_and_description()
+    pub fn set_title<S: Into<Cow<'a, str>>>(&mut self, title: Vec<S>) {
+        self.title = Some(title.into_iter().map(Into::into).collect());
     }
 
-    fn is_alternative_search(&self) -> bool {
-        self.url.contains("search?q=")
-            || self.url.contains("search?ie=")
-            || self.url.contains("&ie=")
-    }
-
-    fn has_title_and_description(&self) -> bool {
-        self.title.is_some()
-            && !self.title.as_ref().unwrap().is_empty()
-            && self.description.is_some()
-            && !self.description.as_ref().unwrap().is_empty()
-    }
+    pub fn add_to_description<S: Into<Cow<'a, str>>>(
+        &mut self,
+        description: Vec<S>,
+    ) {
+        let description = description.into_iter().map(Into::into);
 
-    fn is_google_logo(&self) -> bool {
-        self.title == Some(vec!["G", "o", "o", "g", "l", "e"])
+        match self.description.as_mut() {
+            Some(existing) => existing.extend(description),
+            None => self.description = Some(description.collect()),
+        }
     }
 
-    fn is_image_link(&self) -> bool {
-        self.url.contains("sca_esv")
+    pub fn into_owned(self) -> SearchResultOwned {
+        SearchResult {
+            url: Cow::Owned(self.url.into_owned()),
+            title: self.title.map(owned_copy),
+            description: self.description.map(owned_copy),
+            display_url: self.display_url.map(owned),
+            date: self.date.map(owned),
+            snippet: self.snippet.map(owned),
+            sitelinks: self
+                .sitelinks
+                .into_iter()
+                .map(|sitelink| Sitelink {
+                    title: owned(sitelink.title),
+                    url: owned(sitelink.url),
+                })
+                .collect(),
+            annotations: owned_copy(self.annotations),
+        }
     }
 
-    fn is_google_ad(&self) -> bool {
-        self.url.starts_with("http://www.google.com/aclk?")
-            || self.url.starts_with("https://www.google.com/aclk?")
+    /// A borrowed view of this result, e.g. to filter cached results
+    /// without cloning them.
+    pub fn borrowed(&self) -> SearchResult<'_> {
+        SearchResult {
+            url: Cow::Borrowed(&self.url),
+            title: self.title.as_deref().map(borrowed_copy),
+            description: self.description.as_deref().map(borrowed_copy),
+            display_url: self.display_url.as_deref().map(Cow::Borrowed),
+            date: self.date.as_deref().map(Cow::Borrowed),
+            snippet: self.snippet.as_deref().map(Cow::Borrowed),
+            sitelinks: self
+                .sitelinks
+                .iter()
+                .map(|sitelink| Sitelink {
+                    title: Cow::Borrowed(&sitelink.title),
+                    url: Cow::Borrowed(&sitelink.url),
+                })
+                .collect(),
+            annotations: borrowed_copy(&self.annotations),
+        }
     }
 
-    fn is_google_logistics(&self) -> bool {
-        match &self.title {
-            Some(title) => {
-                (title.join(" ") == "Privacy")
-                    || (title.join(" ") == "Learn more")
-                    || (title.join(" ") == "Settings")
-                    || (title.join(" ") == "Terms")
-                    || (title.join(" ") == "Sign in")
-                    || (title.join(" ") == "Search tools")
-            }
-            None => false,
-        }
+    /// Narrows the url to `url[start..end]`, still borrowing from the page
+    /// when it did before.
+    pub fn slice_url(&mut self, start: usize, end: usize) {
+        self.url = match std::mem::take(&mut self.url) {
+            Cow::Borrowed(url) => Cow::Borrowed(&url[start..end]),
+            Cow::Owned(url) => Cow::Owned(url[start..end].to_string()),
+        };
     }
 
     pub fn web_page(&self) -> &str {
@@ -83,7 +128,7 @@ impl<'a> SearchResult<'a> {
         if segments.len() > 1 {
             segments[0]
         } else {
-            self.url
+            &self.url
         }
     }
 
@@ -93,15 +138,35 @@ impl<'a> SearchResult<'a> {
             None => String::from(""),
         };
 
-        let res = decode(&joined_description).unwrap().to_string();
+        let res = match decode(&joined_description) {
+            Ok(decoded) => decoded.into_owned(),
+            // escapes that aren't UTF-8: show the text as it came
+            Err(_) => joined_description,
+        };
 
         //convert nbsps
-        let res = res.replace(r#"\u{a0}"#, " ");
+        res.replace(r#"\u{a0}"#, " ")
+    }
+}
 
-        res
+impl From<&SearchResult<'_>> for SearchResultOwned {
+    fn from(result: &SearchResult<'_>) -> Self {
+        result.borrowed().into_owned()
     }
 }
 
+fn owned(text: Cow<'_, str>) -> Cow<'static, str> {
+    Cow::Owned(text.into_owned())
+}
+
+fn owned_copy(copy: Vec<Cow<'_, str>>) -> Vec<Cow<'static, str>> {
+    copy.into_iter().map(owned).collect()
+}
+
+fn borrowed_copy<'a>(copy: &'a [Cow<'_, str>]) -> Vec<Cow<'a, str>> {
+    copy.iter().map(|text| Cow::Borrowed(&**text)).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -111,7 +176,7 @@ mod tests {
         let mut result = SearchResult::new(
             "https://www.lowes.com/pl/Cordless--Drills/4294607722?refinement=4294776932",
         );
-        result.title = Some(vec!["Cordless", "Drills"]);
+        result.set_title(vec!["Cordless", "Drills"]);
 
         assert_eq!(
             result.web_page(),
@@ -124,7 +189,7 @@ mod tests {
         let mut result = SearchResult::new(
             "https://en.wikipedia.org/wiki/David_Blough#2015_season",
         );
-        result.title = Some(vec!["David", "Blough"]);
+        result.set_title(vec!["David", "Blough"]);
 
         assert_eq!(
             result.web_page(),
@@ -137,7 +202,7 @@ mod tests {
         let mut result = SearchResult::new(
             "https://en.wikipedia.org/wiki/David_Blough%232015_season",
         );
-        result.title = Some(vec!["David", "Blough"]);
+        result.set_title(vec!["David", "Blough"]);
 
         assert_eq!(
             result.web_page(),
@@ -169,25 +234,50 @@ mod tests {
         // ]);
 
         // input from returned by fastly logging
-        let input = Some(vec![
+        let input = vec![
             "The cat (Felis catus) is referred to as\\u{a0}...",
             "List of cat breeds",
             " · ",
             "Cat anatomy.",
-        ]);
+        ];
 
-        let input_result = SearchResult {
-            url: "foo",
-            title: Some(vec!["foo"]),
-            description: input,
-        };
+        let mut input_result = SearchResult::new("foo");
+        input_result.set_title(vec!["foo"]);
+        input_result.add_to_description(input);
 
-        // result on local server
-        let expected = r#"The cat (Felis catus) is referred to as<> ... List of cat breeds  ·  Cat anatomy."#;
+        // the escaped nbsp comes out as a space
+        let expected = r#"The cat (Felis catus) is referred to as ... List of cat breeds  ·  Cat anatomy."#;
 
         assert_eq!(
             SearchResult::joined_and_decoded_description(&input_result),
             expected
         )
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_into_owned_outlives_page() {
+        let owned: SearchResultOwned = {
+            let page = String::from("https://www.mlb.com/cubs");
+            let mut result = SearchResult::new(page.as_str());
+            result.set_title(vec!["Chicago", "Cubs"]);
+            result.into_owned()
+        };
+
+        assert_eq!(owned.url, "https://www.mlb.com/cubs");
+        assert_eq!(owned.title.unwrap(), vec!["Chicago", "Cubs"]);
+    }
+
+    #[test]
+    fn test_owned_round_trips_through_json() {
+        let mut result = SearchResult::new("https://www.mlb.com/cubs");
+        result.set_title(vec!["Chicago Cubs"]);
+        result.add_to_description(vec!["The official site"]);
+        let owned = SearchResultOwned::from(&result);
+
+        let json = serde_json::to_string(&owned).unwrap();
+        let decoded: SearchResultOwned = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(decoded, owned);
+        assert_eq!(decoded.borrowed(), result);
+    }
+}
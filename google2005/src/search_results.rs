@@ -1,9 +1,47 @@
-use crate::parser::parse;
-use crate::search_result::SearchResult;
-use scraper::Html;
+use crate::filter_rules::FilterRules;
+use crate::preferences::Preferences;
+use crate::search_query::SearchQuery;
+use crate::search_result::{SearchResult, SearchResultOwned};
+use crate::url_cleaner::UrlCleaner;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::ops::Deref;
 
-#[derive(Debug)]
+/// The rule that keeps results which no other rule drops.
+pub const KEPT: &str = "kept";
+
+/// The rule that drops results linking to a page an earlier one links to.
+pub const REDUNDANT: &str = "redundant";
+
+/// The rule that drops results the query's operators rule out, e.g. ones
+/// on a site it excludes.
+pub const QUERY: &str = "query";
+
+/// The rule that drops results on a site the user blocked.
+pub const BLOCKED: &str = "blocked";
+
+/// The rule that keeps results on a site the user pinned, ahead of the
+/// others.
+pub const PINNED: &str = "pinned";
+
+/// The rule that drops results kept after a page was already full. `search`
+/// starts the next page with them.
+pub const OVERFLOW: &str = "overflow";
+
+/// Why `SearchResults::filter_explained` kept or dropped one result.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FilterVerdict {
+    /// The result as the parser produced it.
+    pub result: SearchResultOwned,
+    pub kept: bool,
+    /// `KEPT`, `PINNED`, `REDUNDANT`, `QUERY`, `BLOCKED`, or the name of
+    /// the `FilterRules` rule that dropped it.
+    pub rule: String,
+    /// Where the result links to once cleaned, if it got that far.
+    pub cleaned_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct SearchResults<'a> {
     pub results: Vec<SearchResult<'a>>,
 }
@@ -17,94 +55,271 @@ impl<'a> Deref for SearchResults<'a> {
 }
 
 impl<'a> SearchResults<'a> {
-    pub fn new(dom: &'a Html) -> Self {
+    pub fn new(results: Vec<SearchResult<'a>>) -> Self {
+        SearchResults { results }
+    }
+
+    pub fn into_owned(self) -> SearchResults<'static> {
+        SearchResults {
+            results: self
+                .results
+                .into_iter()
+                .map(SearchResult::into_owned)
+                .collect(),
+        }
+    }
+
+    /// Borrows owned (e.g. cached) results so they can be filtered and
+    /// rendered without cloning.
+    pub fn borrowed(results: &'a [SearchResultOwned]) -> Self {
         SearchResults {
-            results: parse(&dom),
+            results: results.iter().map(SearchResult::borrowed).collect(),
         }
     }
 
     pub fn filter(&mut self) -> &mut Self {
-        self.remove_junk();
+        self.filter_with(&FilterRules::default(), &UrlCleaner::default())
+    }
+
+    pub fn filter_with(
+        &mut self,
+        rules: &FilterRules,
+        cleaner: &UrlCleaner,
+    ) -> &mut Self {
+        self.filter_counting_drops(
+            rules,
+            cleaner,
+            &SearchQuery::default(),
+            &Preferences::default(),
+        );
+
+        self
+    }
+
+    /// Filters like `filter_with`, returning how many results were dropped
+    /// for each reason.
+    pub fn filter_counting_drops(
+        &mut self,
+        rules: &FilterRules,
+        cleaner: &UrlCleaner,
+        query: &SearchQuery,
+        preferences: &Preferences,
+    ) -> BTreeMap<String, usize> {
+        let verdicts =
+            self.filter_explained(rules, cleaner, query, preferences);
+
+        drop_counts(&verdicts)
+    }
+
+    /// Filters like `filter_with`, drops results that `query` rules out,
+    /// then drops and pins results following the user's `preferences`.
+    /// Returns a verdict for every result as it was before filtering, in
+    /// order: the rule that dropped it, or `KEPT` (or `PINNED`) and the URL
+    /// it was cleaned to.
+    pub fn filter_explained(
+        &mut self,
+        rules: &FilterRules,
+        cleaner: &UrlCleaner,
+        query: &SearchQuery,
+        preferences: &Preferences,
+    ) -> Vec<FilterVerdict> {
+        let mut verdicts = self
+            .results
+            .iter()
+            .map(|result| {
+                let junk_reason = rules.junk_reason(result);
+
+                FilterVerdict {
+                    result: result.into(),
+                    kept: junk_reason.is_none(),
+                    rule: junk_reason.unwrap_or(KEPT).to_string(),
+                    cleaned_url: None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        self.remove_junk(rules);
         self.strip_quotes();
-        // self.strip_analytics_bs();
-        // println!("************ after_analytics bs: {:#?}", self);
-        self.remove_redundant_pages();
-
-        if self.len() != 10 {
-            println!(
-                "Error: Should return 10 links, {} links found",
-                self.len()
-            );
+        self.clean_urls(cleaner);
+        let redundant = self.remove_redundant_pages();
+
+        let mut survivors = self.results.iter();
+        let not_junk = verdicts.iter_mut().filter(|verdict| verdict.kept);
+        for (verdict, redundant) in not_junk.zip(redundant) {
+            if redundant {
+                verdict.kept = false;
+                verdict.rule = REDUNDANT.to_string();
+            } else {
+                verdict.cleaned_url =
+                    survivors.next().map(|result| result.url.to_string());
+            }
         }
 
-        self
+        let contradicting = self.remove_contradicting(query);
+
+        let unique = verdicts.iter_mut().filter(|verdict| verdict.kept);
+        for (verdict, contradicting) in unique.zip(contradicting) {
+            if contradicting {
+                verdict.kept = false;
+                verdict.rule = QUERY.to_string();
+            }
+        }
+
+        let preferred = self.apply_preferences(preferences);
+
+        let wanted = verdicts.iter_mut().filter(|verdict| verdict.kept);
+        for (verdict, rule) in wanted.zip(preferred) {
+            if let Some(rule) = rule {
+                verdict.kept = rule != BLOCKED;
+                verdict.rule = rule.to_string();
+            }
+        }
+
+        verdicts
+    }
+
+    // Runs on cleaned URLs. Whether each result was removed for going
+    // against the query.
+    fn remove_contradicting(&mut self, query: &SearchQuery) -> Vec<bool> {
+        let contradicting = self
+            .results
+            .iter()
+            .map(|result| query.contradicted_by(result))
+            .collect::<Vec<_>>();
+
+        let mut removed = contradicting.iter();
+        self.results.retain(|_| !removed.next().unwrap());
+
+        contradicting
+    }
+
+    // Runs last, on cleaned URLs. Returns `BLOCKED` or `PINNED` for each
+    // result that was blocked or pinned, in their order before pinned ones
+    // were moved to the top.
+    fn apply_preferences(
+        &mut self,
+        preferences: &Preferences,
+    ) -> Vec<Option<&'static str>> {
+        let preferred = self
+            .results
+            .iter()
+            .map(|result| {
+                if preferences.is_blocked(&result.url) {
+                    Some(BLOCKED)
+                } else if preferences.is_pinned(&result.url) {
+                    Some(PINNED)
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut results = std::mem::take(&mut self.results)
+            .into_iter()
+            .zip(&preferred)
+            .filter(|(_, rule)| **rule != Some(BLOCKED))
+            .collect::<Vec<_>>();
+
+        // a stable sort, so pinned results keep their order
+        results.sort_by_key(|(_, rule)| **rule != Some(PINNED));
+        self.results = results.into_iter().map(|(result, _)| result).collect();
+
+        preferred
     }
 
-    fn remove_junk(&mut self) {
+    /// Drops the results after the first `count`, returning them.
+    pub fn keep_first(&mut self, count: usize) -> Vec<SearchResult<'a>> {
+        self.results.split_off(count.min(self.results.len()))
+    }
+
+    fn remove_junk(&mut self, rules: &FilterRules) {
         self.results
-            .retain(|search_result| search_result.is_regular_result());
+            .retain(|search_result| rules.is_regular_result(search_result));
     }
 
-    // TODO restore this and move it out of filter
-    // fn strip_analytics_bs(&mut self) {
-    //     for result in self.results.iter_mut() {
-    //         result.url = between("url?q=", "&sa=U", result.url);
-    //     }
-    // }
+    // runs after remove_junk, which recognizes ads by their aclk wrapper
+    fn clean_urls(&mut self, cleaner: &UrlCleaner) {
+        for result in self.results.iter_mut() {
+            if let Some(url) = cleaner.clean(&result.url) {
+                if url != result.url {
+                    result.url = url.into();
+                }
+            }
+
+            for sitelink in result.sitelinks.iter_mut() {
+                if let Some(url) = cleaner.clean(&sitelink.url) {
+                    if url != sitelink.url {
+                        sitelink.url = url.into();
+                    }
+                }
+            }
+        }
+    }
 
     // TODO delete this when google2005lambda is solved
     fn strip_quotes(&mut self) {
         for result in self.results.iter_mut() {
-            if result.url.len() > 0 && result.url.as_bytes()[0] == 92 {
-                result.url = &result.url[1..];
+            let url = result.url.as_bytes();
+            let (mut start, mut end) = (0, url.len());
+
+            if start < end && url[start] == 92 {
+                start += 1;
+            }
+
+            if start < end && url[end - 1] == 34 {
+                end -= 1;
             }
 
-            if result.url.len() > 0
-                && result.url.as_bytes()[result.url.len() - 1] == 34
-            {
-                result.url = &result.url[..result.url.len() - 1];
+            if start < end && url[start] == 34 {
+                start += 1;
             }
 
-            if result.url.len() > 0 && result.url.as_bytes()[0] == 34 {
-                result.url = &result.url[1..];
+            if start < end && url[end - 1] == 92 {
+                end -= 1;
             }
 
-            if result.url.len() > 0
-                && result.url.as_bytes()[result.url.len() - 1] == 92
-            {
-                result.url = &result.url[..result.url.len() - 1];
+            if (start, end) != (0, url.len()) {
+                result.slice_url(start, end);
             }
         }
     }
 
-    fn remove_redundant_pages(&mut self) {
+    // Whether each result was removed for pointing at the same page as an
+    // earlier one
+    fn remove_redundant_pages(&mut self) -> Vec<bool> {
         let mut unique_pages: Vec<String> = vec![];
+        let redundant = self
+            .results
+            .iter()
+            .map(|result| {
+                let page = result.web_page().to_string();
+                let redundant = unique_pages.contains(&page);
+                if !redundant {
+                    unique_pages.push(page);
+                }
 
-        self.results.retain(|result| {
-            if unique_pages.contains(&result.web_page().to_string()) {
-                false
-            } else {
-                unique_pages.push(result.web_page().to_string());
-                true
-            }
-        });
+                redundant
+            })
+            .collect::<Vec<_>>();
+
+        let mut removed = redundant.iter();
+        self.results.retain(|_| !removed.next().unwrap());
+
+        redundant
     }
 }
 
-#[allow(dead_code)]
-fn between<'a, 'b>(start: &'a str, end: &'a str, s: &'b str) -> &'b str {
-    let start_index = s.find(start).unwrap_or(0);
-    let end_index = s.find(end).unwrap_or(s.len());
-    let between = &s[start_index + start.len()..end_index];
-    between
-}
+/// How many of `verdicts` dropped their result, for each reason.
+pub(crate) fn drop_counts(
+    verdicts: &[FilterVerdict],
+) -> BTreeMap<String, usize> {
+    let mut dropped = BTreeMap::new();
+
+    for verdict in verdicts.iter().filter(|verdict| !verdict.kept) {
+        *dropped.entry(verdict.rule.clone()).or_insert(0) += 1;
+    }
 
-#[allow(dead_code)]
-fn bookended_with(start: &str, end: &str, s: &str) -> String {
-    let start_index = s.find(start).unwrap() - start.len();
-    let end_index = s.find(end).unwrap() + end.len();
-    let between = &s[start_index + start.len()..end_index];
-    between.to_string()
+    dropped
 }
 
 #[cfg(test)]
@@ -125,13 +340,35 @@ mod test {
     }
 
     #[test]
-    fn test_between() {
-        let input = "http://localhost:7878/url?q=https://www.sbnation.com/authors/jon-bois&sa=U&ved=2ahUKEwj91bWk2IT3AhV1omoFHTGiCBgQFnoECAwQAg&usg=AOvVaw0tLu83JeMGMgnFF9iLD2uA";
+    fn test_clean_urls() {
+        let input = "/url?q=https://www.sbnation.com/authors/jon-bois&sa=U&ved=2ahUKEwj91bWk2IT3AhV1omoFHTGiCBgQFnoECAwQAg&usg=AOvVaw0tLu83JeMGMgnFF9iLD2uA";
+        let mut results = SearchResults {
+            results: vec![SearchResult::new(input)],
+        };
+
+        results.clean_urls(&UrlCleaner::default());
+
+        assert_eq!(results[0].url, "https://www.sbnation.com/authors/jon-bois")
+    }
+
+    #[test]
+    fn test_clean_urls_merges_redundant_pages() {
+        let mut results = SearchResults {
+            results: vec![
+                SearchResult::new("/url?q=https://www.mlb.com/cubs&sa=U"),
+                SearchResult::new(
+                    "https://www.mlb.com/cubs?utm_source=google",
+                ),
+            ],
+        };
+
+        results.clean_urls(&UrlCleaner::default());
+        results.remove_redundant_pages();
 
         assert_eq!(
-            between("url?q=", "&sa=U", input),
-            "https://www.sbnation.com/authors/jon-bois"
-        )
+            results.iter().map(|l| &*l.url).collect::<Vec<&str>>(),
+            vec!["https://www.mlb.com/cubs"]
+        );
     }
 
     #[test]
@@ -139,23 +376,20 @@ mod test {
         let analytics_link = "http://localhost:7878/url?q=https://www.sbnation.com/authors/jon-bois&sa=U&ved=2ahUKEwj91bWk2IT3AhV1omoFHTGiCBgQFnoECAwQAg&usg=AOvVaw0tLu83JeMGMgnFF9iLD2uA";
         let shitty_quoted_link = r#"\"https://www.wikipedia.org/\""#;
 
+        let result = |url: &'static str| {
+            let mut result = SearchResult::new(url);
+            result.set_title(vec!["Jon Bois"]);
+            result.add_to_description(vec!["Articles by Jon Bois"]);
+            result
+        };
+
         let mut input = SearchResults {
-            results: vec![
-                SearchResult::new(analytics_link),
-                SearchResult::new(shitty_quoted_link),
-            ],
+            results: vec![result(analytics_link), result(shitty_quoted_link)],
         };
 
         assert_eq!(input.filter().len(), 2);
     }
 
-    #[test]
-    fn test_bookended_with() {
-        let input = "fooo<a>bar</a>baz";
-
-        assert_eq!(bookended_with("<a>", "</a>", input), "<a>bar</a>")
-    }
-
     #[test]
     fn test_redundant_pages() {
         let mut search_results = SearchResults {
@@ -181,7 +415,7 @@ mod test {
             search_results
                 .results
                 .iter()
-                .map(|l| l.url)
+                .map(|l| &*l.url)
                 .collect::<Vec<&str>>(),
             vec![
                 "https://en.wikipedia.org/wiki/David_Blough#2015_season",
@@ -189,4 +423,136 @@ mod test {
             ]
         )
     }
+
+    #[test]
+    fn test_filter_explained() {
+        let result = |url: &'static str| {
+            let mut result = SearchResult::new(url);
+            result.set_title(vec!["Chicago Cubs"]);
+            result.add_to_description(vec!["The official site"]);
+            result
+        };
+        let mut results = SearchResults::new(vec![
+            result("/url?q=https://www.mlb.com/cubs&sa=U"),
+            result("https://www.google.com/aclk?sa=l&adurl=https://tix.com"),
+            result("https://www.mlb.com/cubs?utm_source=google"),
+            SearchResult::new("https://www.cubs.com"),
+        ]);
+
+        let verdicts = results.filter_explained(
+            &FilterRules::default(),
+            &UrlCleaner::default(),
+            &SearchQuery::default(),
+            &Preferences::default(),
+        );
+
+        assert_eq!(
+            verdicts
+                .iter()
+                .map(|verdict| (verdict.kept, verdict.rule.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (true, KEPT),
+                (false, "google_ad"),
+                (false, REDUNDANT),
+                (false, "missing_title_or_description"),
+            ]
+        );
+        assert_eq!(
+            verdicts[0].result.url,
+            "/url?q=https://www.mlb.com/cubs&sa=U"
+        );
+        assert_eq!(
+            verdicts[0].cleaned_url.as_deref(),
+            Some("https://www.mlb.com/cubs")
+        );
+        assert_eq!(verdicts[2].cleaned_url, None);
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn test_filter_applies_preferences() {
+        let result = |url: &'static str| {
+            let mut result = SearchResult::new(url);
+            result.set_title(vec!["Cubs"]);
+            result.add_to_description(vec!["Chicago Cubs"]);
+            result
+        };
+        let mut results = SearchResults::new(vec![
+            result("/url?q=https://www.pinterest.com/cubs/&sa=U"),
+            result("https://en.wikipedia.org/wiki/Chicago_Cubs"),
+            result("https://www.mlb.com/cubs"),
+        ]);
+        let preferences =
+            Preferences::from_form("blocked=pinterest.com&pinned=mlb.com");
+
+        let verdicts = results.filter_explained(
+            &FilterRules::default(),
+            &UrlCleaner::default(),
+            &SearchQuery::default(),
+            &preferences,
+        );
+
+        assert_eq!(
+            verdicts
+                .iter()
+                .map(|verdict| (verdict.kept, verdict.rule.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(false, BLOCKED), (true, KEPT), (true, PINNED)]
+        );
+        assert_eq!(
+            results.iter().map(|l| &*l.url).collect::<Vec<&str>>(),
+            vec![
+                "https://www.mlb.com/cubs",
+                "https://en.wikipedia.org/wiki/Chicago_Cubs"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_enforces_query() {
+        let result = |url: &'static str| {
+            let mut result = SearchResult::new(url);
+            result.set_title(vec!["Cubs"]);
+            result.add_to_description(vec!["Chicago Cubs"]);
+            result
+        };
+        let mut results = SearchResults::new(vec![
+            result("/url?q=https://www.pinterest.com/cubs/&sa=U"),
+            result("https://en.wikipedia.org/wiki/Chicago_Cubs"),
+            result("https://www.mlb.com/cubs"),
+        ]);
+        let query = SearchQuery::parse("cubs -pinterest site:wikipedia.org");
+
+        let verdicts = results.filter_explained(
+            &FilterRules::default(),
+            &UrlCleaner::default(),
+            &query,
+            &Preferences::default(),
+        );
+
+        assert_eq!(
+            verdicts
+                .iter()
+                .map(|verdict| (verdict.kept, verdict.rule.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(false, QUERY), (true, KEPT), (false, QUERY)]
+        );
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn test_filter_accepts_borrowed_owned_results() {
+        let mut result =
+            SearchResult::new(String::from(r#"\"https://www.mlb.com/cubs\""#));
+        result.set_title(vec![String::from("Chicago Cubs")]);
+        result.add_to_description(vec![String::from("The official site")]);
+        let cached: Vec<SearchResultOwned> = vec![result];
+
+        let mut results = SearchResults::borrowed(&cached);
+
+        assert_eq!(results.filter().len(), 1);
+        assert_eq!(results[0].url, "https://www.mlb.com/cubs");
+        assert_eq!(cached[0].url, r#"\"https://www.mlb.com/cubs\""#);
+    }
 }
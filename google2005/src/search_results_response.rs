@@ -1,17 +1,61 @@
 use askama::Template;
 use serde::Serialize;
-use urlencoding::decode;
+use std::time::SystemTime;
+use urlencoding::{decode, encode};
 
 use crate::{
-    search_request::SearchRequest, search_results::SearchResults,
-    utils::google2005_error::Google2005Error,
+    backfill::UpstreamCursor,
+    health_report::HealthReport,
+    preferences::{FormToken, Preferences},
+    response_format::ResponseFormat,
+    search_engine_parser::PageMetadata,
+    search_query::{SearchQuery, TextSpan},
+    search_request::{SafeSearch, SearchRequest, TimeRange, MAX_START},
+    search_results::SearchResults,
+    utils::{google2005_error::Google2005Error, hostnames, timestamp},
 };
 
+#[derive(Debug, Serialize)]
+struct DecodedSitelink {
+    title: String,
+    /// Left encoded, as scraped and cleaned.
+    url: String,
+}
+
 #[derive(Debug, Serialize)]
 struct DecodedResult {
     url: String,
     title: String,
     description: String,
+    /// The green URL line, 2005 style: `www.mlb.com/cubs`.
+    display_url: String,
+    date: Option<String>,
+    sitelinks: Vec<DecodedSitelink>,
+    annotations: Vec<String>,
+    /// `title`, with the query's words highlighted.
+    title_spans: Vec<TextSpan>,
+    /// `description`, with the query's words highlighted.
+    description_spans: Vec<TextSpan>,
+    /// What a "block this site" form blocks.
+    #[serde(skip)]
+    site: Option<String>,
+}
+
+/// One of the numbered links to other pages of the search.
+#[derive(Debug)]
+struct PageLink {
+    number: u16,
+    link: String,
+    current: bool,
+}
+
+/// One choice on the "Search tools" bar: a link to the first page of the
+/// same search with that choice made.
+#[derive(Debug)]
+struct SearchTool {
+    label: &'static str,
+    link: String,
+    selected: bool,
 }
 
 #[derive(Debug, Serialize, Template)]
@@ -19,57 +63,364 @@ struct DecodedResult {
 pub struct SearchResultsResponse {
     results: Vec<DecodedResult>,
     query: String,
+    /// How the query's operators were read, when it has any.
+    #[serde(skip)]
+    interpretation: Option<String>,
+    /// The query Google suggested instead, from its "Did you mean" line.
+    spelling_suggestion: Option<String>,
+    /// Our own search for `spelling_suggestion`.
+    #[serde(skip)]
+    spelling_link: Option<String>,
     first_result: u16,
     next_page_starts: Vec<u16>,
     last_result: u16,
     page_start: u16,
     page: u16,
+    /// The page's `lang` attribute.
+    #[serde(skip)]
+    lang: String,
+    /// The language, region and search tools for links to other pages of
+    /// this search.
+    #[serde(skip)]
+    page_params: String,
+    /// Where the next page starts in Google's results, when known.
+    next_upstream: Option<UpstreamCursor>,
+    /// The search, for links to its other pages.
+    #[serde(skip)]
+    request: SearchRequest,
+    /// The choices on the "Search tools" bar, in groups.
+    #[serde(skip)]
+    search_tools: Vec<Vec<SearchTool>>,
+    #[serde(skip)]
     image_hostname: String,
+    #[serde(skip)]
     stylesheet_hostname: String,
+    /// This page's path, for forms that lead back to it.
+    #[serde(skip)]
+    return_to: String,
+    /// Proves to `/preferences/block` that its forms were posted from this
+    /// page.
+    #[serde(skip)]
+    form_token: String,
+    parser: String,
+    health: HealthReport,
+}
+
+#[derive(Template)]
+#[template(path = "search_rss.xml")]
+struct RssFeed<'a> {
+    response: &'a SearchResultsResponse,
+    search_link: String,
+    feed_link: String,
+}
+
+#[derive(Template)]
+#[template(path = "search_atom.xml")]
+struct AtomFeed<'a> {
+    response: &'a SearchResultsResponse,
+    search_link: String,
+    feed_link: String,
+    updated: String,
 }
 
 impl SearchResultsResponse {
     pub fn new(
         parsed: &SearchResults,
-        query: SearchRequest,
+        metadata: &PageMetadata,
+        query: &SearchRequest,
     ) -> Result<SearchResultsResponse, Google2005Error> {
         let mut results: Vec<DecodedResult> = vec![];
 
-        if parsed.results.len() == 0 {
-            return Err(Google2005Error::new(None, Some("No results found")));
-        }
-
         for result in &parsed.results {
-            // println!("*** result.url: {}", result.url);
-            let decoded_url = decode(result.url).unwrap();
-            // println!("** decoded_url: {}", decoded_url);
-            let joined_title = result.title.as_ref().unwrap().join(" ");
-            let description = result.joined_and_decoded_description();
+            // Custom filter rules can keep untitled links, and an href's
+            // escapes needn't decode to UTF-8. Neither can be shown.
+            let (decoded_url, joined_title) =
+                match (decode(&result.url), &result.title) {
+                    (Ok(url), Some(title)) => (url, title.join(" ")),
+                    _ => {
+                        println!("skipping unshowable result: {}", result.url);
+                        continue;
+                    }
+                };
+            let description = match &result.snippet {
+                Some(snippet) => snippet.to_string(),
+                None => result.joined_and_decoded_description(),
+            };
 
             results.push(DecodedResult {
+                site: Preferences::site(&decoded_url),
+                display_url: Self::display_url(
+                    result.display_url.as_deref(),
+                    &decoded_url,
+                ),
+                date: result.date.as_ref().map(|date| date.to_string()),
+                sitelinks: result
+                    .sitelinks
+                    .iter()
+                    .map(|sitelink| DecodedSitelink {
+                        title: sitelink.title.to_string(),
+                        url: sitelink.url.to_string(),
+                    })
+                    .collect(),
+                annotations: result
+                    .annotations
+                    .iter()
+                    .map(|annotation| annotation.to_string())
+                    .collect(),
                 url: decoded_url.to_string(),
+                title_spans: query.query.highlight(&joined_title),
+                description_spans: query.query.highlight(&description),
                 title: joined_title.to_string(),
-                description: description,
+                description,
             });
         }
 
+        if results.is_empty() {
+            return Err(Google2005Error::NoResults);
+        }
+
         // // turn everything off for now :( 
         // if  results.len() < 2 {
-        //     return Err(Google2005Error::new(None, Some("No results found")));
+        //     return Err(Google2005Error::NoResults);
         // }
 
+        let last_result =
+            Self::response_start(query.start) + results.len() as u16;
+        let page_params = query.page_params();
+        let return_to = format!(
+            "/search?q={}&start={}{}{}",
+            encode(&query.search_string),
+            Self::page_start(query.start),
+            page_params,
+            query.upstream.map(|cursor| cursor.params()).unwrap_or_default()
+        );
+
         Ok(SearchResultsResponse {
-            results: results,
-            query: query.search_string,
+            results,
+            query: query.search_string.clone(),
+            interpretation: query
+                .query
+                .has_operators()
+                .then(|| query.query.describe()),
+            spelling_suggestion: metadata.spelling_suggestion.clone(),
+            spelling_link: metadata
+                .spelling_suggestion
+                .as_deref()
+                .map(|suggestion| Self::spelling_link(query, suggestion)),
             first_result: Self::response_start(query.start),
             next_page_starts: Self::next_page_starts(query.start),
-            last_result: Self::response_start(query.start)
-                + parsed.results.len() as u16,
+            last_result,
             page_start: Self::page_start(query.start),
             page: Self::page(query.start),
-            image_hostname: Self::image_hostname(),
-            stylesheet_hostname: Self::stylesheet_hostname(),
+            lang: query.html_lang(),
+            page_params,
+            next_upstream: None,
+            request: query.clone(),
+            search_tools: Self::search_tools(query),
+            image_hostname: hostnames::image_hostname(),
+            stylesheet_hostname: hostnames::stylesheet_hostname(),
+            return_to,
+            form_token: String::new(),
+            parser: metadata.parser.to_string(),
+            health: HealthReport::default(),
+        })
+    }
+
+    pub fn with_health(mut self, health: HealthReport) -> Self {
+        self.health = health;
+
+        self
+    }
+
+    /// Makes the link to the next page start at `cursor` in Google's
+    /// results, or drops it when Google has no more pages.
+    pub fn with_next_page(mut self, cursor: Option<UpstreamCursor>) -> Self {
+        match cursor {
+            Some(cursor) => self.next_upstream = Some(cursor),
+            None => {
+                let page_start = self.page_start;
+                self.next_page_starts.retain(|start| *start <= page_start);
+            }
+        }
+
+        self
+    }
+
+    /// Lets the page's "Block" forms post `token`.
+    pub fn with_form_token(mut self, token: &FormToken) -> Self {
+        self.form_token = token.value.clone();
+
+        self
+    }
+
+    pub fn health(&self) -> &HealthReport {
+        &self.health
+    }
+
+    /// Renders the page as `search.html` or as a feed of the same results.
+    pub fn render_as(
+        &self,
+        format: ResponseFormat,
+    ) -> Result<String, Google2005Error> {
+        let rendered = match format {
+            ResponseFormat::Html => self.render()?,
+            ResponseFormat::Rss => RssFeed {
+                response: self,
+                search_link: self.search_link(ResponseFormat::Html),
+                feed_link: self.search_link(format),
+            }
+            .render()?,
+            ResponseFormat::Atom => AtomFeed {
+                response: self,
+                search_link: self.search_link(ResponseFormat::Html),
+                feed_link: self.search_link(format),
+                updated: timestamp::rfc3339(SystemTime::now()),
+            }
+            .render()?,
+        };
+
+        Ok(rendered)
+    }
+
+    fn page_links(&self) -> Vec<PageLink> {
+        self.next_page_starts
+            .iter()
+            .map(|&start| {
+                let cursor = if start == self.page_start {
+                    self.request.upstream
+                } else if start == self.page_start + 10 {
+                    self.next_upstream
+                } else {
+                    None
+                };
+
+                PageLink {
+                    number: Self::page(start),
+                    link: Self::page_link(&self.request, start, cursor),
+                    current: start == self.page_start,
+                }
+            })
+            .collect()
+    }
+
+    // The same search from `start`, and from `cursor` in Google's results
+    // when that's known. Unless the search says otherwise, the link keeps
+    // the results Google considers similar (`filter=0`).
+    fn page_link(
+        request: &SearchRequest,
+        start: u16,
+        cursor: Option<UpstreamCursor>,
+    ) -> String {
+        let mut page = request.clone();
+        page.filter.get_or_insert_with(|| "0".to_string());
+
+        let mut link = format!("/search?{}", page.to_query_string(start));
+        if let Some(cursor) = cursor {
+            link.push_str(&cursor.params());
+        }
+
+        link
+    }
+
+    fn search_link(&self, format: ResponseFormat) -> String {
+        let mut link = format!(
+            "{}search?q={}&start={}",
+            hostnames::site_hostname(),
+            encode(&self.query),
+            self.page_start
+        );
+        link.push_str(&self.page_params);
+
+        if format != ResponseFormat::Html {
+            link.push_str("&format=");
+            link.push_str(format.param());
+        }
+
+        link
+    }
+
+    // Google's breadcrumb, `www.mlb.com › cubs`, as 2005 showed it:
+    // `www.mlb.com/cubs`. Without one, the URL the result links to.
+    fn display_url(breadcrumb: Option<&str>, url: &str) -> String {
+        let display_url = match breadcrumb {
+            Some(breadcrumb) => breadcrumb.replace(" › ", "/"),
+            None => url.to_string(),
+        };
+
+        ["https://", "http://"]
+            .iter()
+            .find_map(|scheme| display_url.strip_prefix(scheme))
+            .unwrap_or(&display_url)
+            .to_string()
+    }
+
+    // The same search, from its first page, for the suggested query.
+    fn spelling_link(request: &SearchRequest, suggestion: &str) -> String {
+        let mut corrected = request.clone();
+        corrected.search_string = suggestion.to_string();
+        corrected.query = SearchQuery::parse(suggestion);
+
+        format!("/search?{}", corrected.to_query_string(0))
+    }
+
+    fn search_tools(request: &SearchRequest) -> Vec<Vec<SearchTool>> {
+        let time_ranges = [None]
+            .into_iter()
+            .chain(TimeRange::ALL.map(Some))
+            .map(|time_range| {
+                let label = match time_range {
+                    None => "Any time",
+                    Some(TimeRange::Hour) => "Past hour",
+                    Some(TimeRange::Day) => "Past 24 hours",
+                    Some(TimeRange::Week) => "Past week",
+                    Some(TimeRange::Month) => "Past month",
+                    Some(TimeRange::Year) => "Past year",
+                };
+
+                Self::search_tool(request, label, |changed| {
+                    changed.time_range = time_range
+                })
+            })
+            .collect();
+
+        let verbatim = [("All results", false), ("Verbatim", true)]
+            .into_iter()
+            .map(|(label, verbatim)| {
+                Self::search_tool(request, label, |changed| {
+                    changed.verbatim = verbatim
+                })
+            })
+            .collect();
+
+        let safe = [
+            ("Default SafeSearch", None),
+            ("SafeSearch off", Some(SafeSearch::Off)),
+            ("Strict SafeSearch", Some(SafeSearch::Strict)),
+        ]
+        .into_iter()
+        .map(|(label, safe)| {
+            Self::search_tool(request, label, |changed| changed.safe = safe)
         })
+        .collect();
+
+        vec![time_ranges, verbatim, safe]
+    }
+
+    // The choice `change` makes to `request`, which is selected when it
+    // changes nothing.
+    fn search_tool(
+        request: &SearchRequest,
+        label: &'static str,
+        change: impl FnOnce(&mut SearchRequest),
+    ) -> SearchTool {
+        let mut changed = request.clone();
+        change(&mut changed);
+
+        SearchTool {
+            label,
+            link: format!("/search?{}", changed.to_query_string(0)),
+            selected: changed == *request,
+        }
     }
 
     fn response_start(requested_start: u16) -> u16 {
@@ -86,38 +437,29 @@ impl SearchResultsResponse {
         }
     }
 
+    // Only the pages whose place in Google's results is known: the first,
+    // this one and the next. The others depend on how many results the
+    // pages between were backfilled with. None past the last `start` a
+    // search may ask for.
     fn next_page_starts(start: u16) -> Vec<u16> {
-        if start < 60 {
-            (0..10_u16).map(|x| x * 10).collect::<Vec<u16>>()
-        } else {
-            let mut starts = vec![];
-
-            let first = start - 60;
-
-            for i in 1..=10 {
-                starts.push(first + i * 10);
-            }
+        let page_start = Self::page_start(start);
+        let mut starts = vec![0, page_start, page_start + 10];
+        starts.dedup();
+        starts.retain(|start| *start <= MAX_START);
 
-            starts
-        }
+        starts
     }
 
     fn page(start: u16) -> u16 {
         (start / 10) + 1
     }
-
-    fn image_hostname() -> String {
-        "https://google2005.s3.us-east-2.amazonaws.com/images/".to_string()
-    }
-
-    fn stylesheet_hostname() -> String {
-        "https://google2005.s3.us-east-2.amazonaws.com/stylesheets/".to_string()
-    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::search_result::SearchResult;
+    use crate::search_results::QUERY;
 
     #[test]
     fn test_page_handles_zero() {
@@ -160,31 +502,335 @@ mod test {
         let start = 0;
         assert_eq!(
             SearchResultsResponse::next_page_starts(start),
-            vec![0, 10, 20, 30, 40, 50, 60, 70, 80, 90],
+            vec![0, 10],
         );
 
-        let start = 40;
+        let start = 10;
         assert_eq!(
             SearchResultsResponse::next_page_starts(start),
-            vec![0, 10, 20, 30, 40, 50, 60, 70, 80, 90],
+            vec![0, 10, 20],
         );
 
-        let start = 50;
+        let start = 45;
         assert_eq!(
             SearchResultsResponse::next_page_starts(start),
-            vec![0, 10, 20, 30, 40, 50, 60, 70, 80, 90],
+            vec![0, 40, 50],
         );
 
-        let start = 60;
+        let start = 990;
         assert_eq!(
             SearchResultsResponse::next_page_starts(start),
-            vec![10, 20, 30, 40, 50, 60, 70, 80, 90, 100],
+            vec![0, 990],
+        );
+    }
+
+    fn cubs_response() -> SearchResultsResponse {
+        let page = concat!(
+            "<html><head><title>cubs - Google Search</title></head><body>",
+            r#"<a href="https://www.mlb.com/cubs"><h3>Cubs & Sox</h3></a>"#,
+            "<span>The official site of the Chicago Cubs</span>",
+            "</body></html>"
         );
 
-        let start = 100;
+        let request =
+            SearchRequest::parse("q=chicago+cubs&start=10").unwrap();
+
+        crate::scrape(&request, page).unwrap()
+    }
+
+    #[test]
+    fn test_render_in_search_language() {
+        let page = concat!(
+            "<html><body>",
+            r#"<a href="https://www.mlb.com/cubs"><h3>Cubs</h3></a>"#,
+            "<span>Site oficial do Chicago Cubs</span>",
+            "</body></html>"
+        );
+        let request = SearchRequest::parse("q=cubs&hl=pt-BR&gl=br").unwrap();
+
+        let html = crate::scrape(&request, page).unwrap().render().unwrap();
+
+        assert!(html.contains(r#"<html lang="pt-BR">"#));
+        assert!(html.contains("q=cubs&amp;hl=pt-BR&amp;gl=br&amp;filter=0"));
+    }
+
+    #[test]
+    fn test_render_search_tools() {
+        let page = concat!(
+            "<html><body>",
+            r#"<a href="https://www.mlb.com/cubs"><h3>Cubs</h3></a>"#,
+            "<span>The official site of the Chicago Cubs</span>",
+            "</body></html>"
+        );
+        let request =
+            SearchRequest::parse("q=cubs&tbs=qdr:w&safe=active&start=10")
+                .unwrap();
+
+        let html = crate::scrape(&request, page).unwrap().render().unwrap();
+
+        assert!(html.contains("<b>Past week</b>"));
+        assert!(html.contains("<b>All results</b>"));
+        assert!(html.contains("<b>Strict SafeSearch</b>"));
+        assert!(html.contains(concat!(
+            r#"<a href="/search?q=cubs&amp;safe=active&amp;tbs=qdr%3Aw"#,
+            r#"%2Cli%3A1">Verbatim</a>"#
+        )));
+        assert!(html.contains(concat!(
+            r#"<a href="/search?q=cubs&amp;safe=active">"#,
+            "Any time</a>"
+        )));
+        assert!(html.contains(
+            "filter=0&amp;safe=active&amp;tbs=qdr%3Aw&amp;start=20"
+        ));
+    }
+
+    #[test]
+    fn test_render_query_interpretation() {
+        let page = concat!(
+            "<html><body>",
+            r#"<a href="https://www.mlb.com/cubs"><h3>Cubs</h3></a>"#,
+            "<span>The official site of the Chicago Cubs</span>",
+            r#"<a href="https://www.pinterest.com/cubs"><h3>Cubs</h3></a>"#,
+            "<span>Cubs pins</span>",
+            "</body></html>"
+        );
+        let request = SearchRequest::parse("q=cubs+-pinterest").unwrap();
+
+        let response = crate::scrape(&request, page).unwrap();
+        let html = response.render().unwrap();
+
+        assert!(html.contains("(cubs, not pinterest)"));
+        assert!(!html.contains("www.pinterest.com"));
+        assert_eq!(response.health().dropped.get(QUERY), Some(&1));
+    }
+
+    #[test]
+    fn test_render_highlights() {
+        let page = concat!(
+            "<html><body>",
+            r#"<a href="https://www.mlb.com/cubs"><h3>Cubs & Sox</h3></a>"#,
+            "<span>The &lt;official&gt; site of the Chicago Cubs</span>",
+            "</body></html>"
+        );
+        let request = SearchRequest::parse("q=cubs+%22the+%3Cofficial%3E%22")
+            .unwrap();
+
+        let html = crate::scrape(&request, page).unwrap().render().unwrap();
+
+        assert!(html.contains("<b>Cubs</b> &amp; Sox"));
+        assert!(html.contains(
+            "<b>The &lt;official</b>&gt; site of the Chicago <b>Cubs</b>"
+        ));
+    }
+
+    #[test]
+    fn test_render_rich_snippet() {
+        let page = concat!(
+            "<html><body><div>",
+            r#"<a href="/url?q=https://www.mlb.com/cubs&amp;sa=U">"#,
+            "<h3><div>Official Chicago Cubs Website</div></h3>",
+            "<div>https://www.mlb.com &#8250; cubs</div>",
+            "</a>",
+            "<div><span>21 hours ago &#183; ",
+            "Get the latest news on the Chicago Cubs</span></div>",
+            "<div><span>$19.99</span></div>",
+            r#"<a href="/url?q=https://www.mlb.com/cubs/roster&amp;sa=U">"#,
+            "Roster</a>",
+            r#"<a href="https://www.mlb.com/cubs/schedule">Schedule</a>"#,
+            "</div></body></html>",
+        );
+        let request = SearchRequest::parse("q=news").unwrap();
+
+        let html = crate::scrape(&request, page).unwrap().render().unwrap();
+
+        assert!(html.contains(
+            r#"<span class="search-result-url">www.mlb.com/cubs</span>"#
+        ));
+        assert!(html.contains(concat!(
+            r#"<span class="search-result-date">21 hours ago - </span>"#,
+            "Get the latest <b>news</b> on the Chicago Cubs"
+        )));
+        assert!(html.contains(
+            r#"<span class="search-result-annotation">$19.99</span>"#
+        ));
+        assert!(html.contains(concat!(
+            r#"<a href="https://www.mlb.com/cubs/roster">Roster</a> - "#,
+            r#"<a href="https://www.mlb.com/cubs/schedule">Schedule</a>"#
+        )));
+    }
+
+    #[test]
+    fn test_render_keeps_sitelink_urls_in_attribute() {
+        let page = concat!(
+            "<html><body><div>",
+            r#"<a href="https://www.mlb.com/cubs"><h3>Cubs</h3></a>"#,
+            "<span>The official site of the Chicago Cubs</span>",
+            r#"<a href="https://www.mlb.com/x%20onmouseover=alert(1)">"#,
+            "Roster</a>",
+            "</div></body></html>",
+        );
+        let request = SearchRequest::parse("q=cubs").unwrap();
+
+        let html = crate::scrape(&request, page).unwrap().render().unwrap();
+
+        assert!(html.contains(
+            r#"<a href="https://www.mlb.com/x%20onmouseover=alert(1)">"#
+        ));
+        assert!(!html.contains(" onmouseover=alert(1)"));
+    }
+
+    #[test]
+    fn test_render_spelling_suggestion() {
+        let page = concat!(
+            "<html><body>",
+            "<div><span>Did you mean: </span>",
+            r#"<a href="/search?q=chicago+cubs&amp;spell=1&amp;sa=X">"#,
+            "<b><i>chicago</i></b> cubs</a></div>",
+            r#"<a href="https://www.mlb.com/cubs"><h3>Cubs</h3></a>"#,
+            "<span>The official site of the Chicago Cubs</span>",
+            "</body></html>"
+        );
+        let request =
+            SearchRequest::parse("q=chicgo+cubs&start=10&hl=en").unwrap();
+
+        let response = crate::scrape(&request, page).unwrap();
+        let html = response.render().unwrap();
+
         assert_eq!(
-            SearchResultsResponse::next_page_starts(start),
-            vec![50, 60, 70, 80, 90, 100, 110, 120, 130, 140],
+            response.spelling_suggestion.as_deref(),
+            Some("chicago cubs")
+        );
+        assert!(html.contains(concat!(
+            r#"<a href="/search?q=chicago+cubs&amp;hl=en">"#,
+            "<b><i>chicago cubs</i></b></a>"
+        )));
+        assert_eq!(response.results.len(), 1);
+    }
+
+    #[test]
+    fn test_render_next_page_from_upstream_cursor() {
+        let html = cubs_response()
+            .with_next_page(Some(UpstreamCursor { start: 20, skip: 3 }))
+            .render()
+            .unwrap();
+
+        assert!(html.contains(concat!(
+            r#"href="/search?q=chicago+cubs&amp;filter=0&amp;start=20"#,
+            r#"&amp;ustart=20&amp;uskip=3">"#
+        )));
+        assert!(html.contains(
+            r#"href="/search?q=chicago+cubs&amp;filter=0&amp;start=10">"#
+        ));
+        assert!(
+            html.contains(r#"href="/search?q=chicago+cubs&amp;filter=0">"#)
+        );
+        // no way to know where the page after next starts
+        assert!(!html.contains("start=30"));
+    }
+
+    #[test]
+    fn test_render_page_link_with_own_cursor() {
+        let page = concat!(
+            "<html><head><title>cubs - Google Search</title></head><body>",
+            r#"<a href="https://www.mlb.com/cubs"><h3>Cubs</h3></a>"#,
+            "<span>The official site of the Chicago Cubs</span>",
+            "</body></html>"
+        );
+        let request =
+            SearchRequest::parse("q=cubs&start=10&ustart=10&uskip=6").unwrap();
+
+        let html = crate::scrape(&request, page).unwrap().render().unwrap();
+
+        assert!(html.contains("start=10&amp;ustart=10&amp;uskip=6"));
+    }
+
+    #[test]
+    fn test_render_page_links_keep_the_search() {
+        let page = concat!(
+            "<html><body>",
+            r#"<a href="https://www.mlb.com/cubs"><h3>Cubs</h3></a>"#,
+            "<span>The official site of the Chicago Cubs</span>",
+            "</body></html>"
         );
+        let request = SearchRequest::parse(
+            "q=cubs+%26+sox+%231+c%2B%2B&num=20&filter=1&start=10",
+        )
+        .unwrap();
+
+        let html = crate::scrape(&request, page).unwrap().render().unwrap();
+
+        assert!(html.contains(concat!(
+            r#"href="/search?q=cubs+%26+sox+%231+c%2B%2B"#,
+            r#"&amp;num=20&amp;filter=1&amp;start=20">"#
+        )));
+    }
+
+    #[test]
+    fn test_no_next_page_link_past_google() {
+        let response = cubs_response().with_next_page(None);
+
+        assert_eq!(response.next_page_starts, vec![0, 10]);
+        assert_eq!(response.next_upstream, None);
+    }
+
+    #[test]
+    fn test_skips_unshowable_results() {
+        let mut cubs = SearchResult::new("https://www.mlb.com/cubs");
+        cubs.set_title(vec!["Cubs"]);
+        let mut undecodable = SearchResult::new("https://example.com/%FF");
+        undecodable.set_title(vec!["Bad escapes"]);
+        let untitled = SearchResult::new("https://example.com/untitled");
+        let request = SearchRequest::parse("q=cubs").unwrap();
+
+        let results = SearchResults::new(vec![undecodable, cubs, untitled]);
+        let response = SearchResultsResponse::new(
+            &results,
+            &PageMetadata::default(),
+            &request,
+        )
+        .unwrap();
+
+        assert_eq!(response.results.len(), 1);
+        assert_eq!(response.results[0].url, "https://www.mlb.com/cubs");
+        assert_eq!(response.last_result, 2);
+
+        let untitled = SearchResult::new("https://example.com/untitled");
+        let results = SearchResults::new(vec![untitled]);
+        assert!(matches!(
+            SearchResultsResponse::new(
+                &results,
+                &PageMetadata::default(),
+                &request,
+            ),
+            Err(Google2005Error::NoResults)
+        ));
+    }
+
+    #[test]
+    fn test_render_rss() {
+        let rss = cubs_response().render_as(ResponseFormat::Rss).unwrap();
+
+        assert!(rss.contains(r#"<rss version="2.0""#));
+        assert!(rss.contains("<title>chicago cubs - Google2005</title>"));
+        assert!(rss.contains("search?q=chicago%20cubs&amp;start=10</link>"));
+        assert!(rss.contains("start=10&amp;format=rss"));
+        assert!(rss.contains("<title>Cubs &amp; Sox</title>"));
+        assert!(rss.contains("<link>https://www.mlb.com/cubs</link>"));
+        assert!(rss.contains(
+            "<description>The official site of the Chicago Cubs</description>"
+        ));
+    }
+
+    #[test]
+    fn test_render_atom() {
+        let atom = cubs_response().render_as(ResponseFormat::Atom).unwrap();
+
+        assert!(atom.contains(r#"<feed xmlns="http://www.w3.org/2005/Atom">"#));
+        assert!(atom.contains("start=10&amp;format=atom"));
+        assert!(atom.contains("<id>https://www.mlb.com/cubs</id>"));
+        assert!(atom.contains(
+            "<summary>The official site of the Chicago Cubs</summary>"
+        ));
+        assert_eq!(atom.matches("<updated>").count(), 2);
     }
 }
@@ -0,0 +1,266 @@
+use url::{form_urlencoded, Url};
+
+/// Query parameters that only exist to track clicks. A trailing `*` matches
+/// any parameter starting with the rest, e.g. `utm_*`.
+pub const DEFAULT_TRACKING_PARAMS: &[&str] = &[
+    "utm_*", "gclid", "gclsrc", "dclid", "fbclid", "msclkid", "mc_cid",
+    "mc_eid", "igshid", "yclid", "srsltid", "_hsenc", "_hsmi", "mkt_tok",
+];
+
+// (host, path, parameter holding the destination) for click-trackers that
+// wrap the real link. Hosts match themselves and their subdomains.
+const REDIRECTORS: &[(&str, &str, &str)] = &[
+    ("google.com", "/aclk", "adurl"),
+    ("google.com", "/imgres", "imgrefurl"),
+    ("facebook.com", "/l.php", "u"),
+    ("youtube.com", "/redirect", "q"),
+    ("out.reddit.com", "/", "url"),
+    ("t.umblr.com", "/redirect", "z"),
+    ("slack-redir.net", "/link", "url"),
+];
+
+// Relative links on a results page are relative to the engine.
+pub(crate) const RESULTS_PAGE_BASE: &str = "https://www.google.com/";
+
+// Redirect wrappers are sometimes nested; stop unwrapping after this many.
+const MAX_UNWRAPS: usize = 5;
+
+/// Turns a scraped href into the canonical URL of the page it leads to:
+/// redirect wrappers are unwrapped and tracking parameters removed.
+#[derive(Debug, Clone)]
+pub struct UrlCleaner {
+    tracking_params: Vec<String>,
+}
+
+impl Default for UrlCleaner {
+    fn default() -> Self {
+        UrlCleaner::new(DEFAULT_TRACKING_PARAMS.iter().map(|p| p.to_string()))
+    }
+}
+
+impl UrlCleaner {
+    pub fn new(tracking_params: impl IntoIterator<Item = String>) -> Self {
+        UrlCleaner {
+            tracking_params: tracking_params.into_iter().collect(),
+        }
+    }
+
+    /// Returns `None` when `href` can't be parsed as a URL, in which case
+    /// callers should keep it as it was.
+    pub fn clean(&self, href: &str) -> Option<String> {
+        let base = Url::parse(RESULTS_PAGE_BASE).unwrap();
+        let mut url = base.join(href).ok()?;
+
+        for _ in 0..MAX_UNWRAPS {
+            match redirect_target(&url) {
+                Some(target) => url = target,
+                None => break,
+            }
+        }
+
+        self.strip_tracking_params(&mut url);
+
+        Some(url.to_string())
+    }
+
+    // The kept parameters stay exactly as they were written: re-encoding
+    // them, `%20` as `+` say, would change the URL.
+    fn strip_tracking_params(&self, url: &mut Url) {
+        if !url.query_pairs().any(|(key, _)| self.is_tracking_param(&key)) {
+            return;
+        }
+
+        let kept = url
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| {
+                form_urlencoded::parse(pair.as_bytes())
+                    .next()
+                    .is_some_and(|(key, _)| !self.is_tracking_param(&key))
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+
+        if kept.is_empty() {
+            url.set_query(None);
+        } else {
+            url.set_query(Some(&kept));
+        }
+    }
+
+    fn is_tracking_param(&self, key: &str) -> bool {
+        self.tracking_params
+            .iter()
+            .any(|param| match param.strip_suffix('*') {
+                Some(prefix) => key.starts_with(prefix),
+                None => key == param,
+            })
+    }
+}
+
+fn redirect_target(url: &Url) -> Option<Url> {
+    let host = url.host_str()?;
+
+    let param = if url.path() == "/url" && is_google_host(host) {
+        // Google's result redirector. Relative hrefs resolve to Google.
+        ["q", "url"].as_slice()
+    } else {
+        let (_, _, param) = REDIRECTORS.iter().find(|(domain, path, _)| {
+            url.path() == *path && is_on_domain(host, domain)
+        })?;
+
+        std::slice::from_ref(param)
+    };
+
+    url.query_pairs()
+        .filter(|(key, _)| param.contains(&key.as_ref()))
+        .find_map(|(_, value)| Url::parse(&value).ok())
+        .filter(|target| ["http", "https"].contains(&target.scheme()))
+}
+
+// google.com, www.google.co.uk, google.de and the like
+fn is_google_host(host: &str) -> bool {
+    let suffix = host
+        .strip_prefix("google.")
+        .or_else(|| host.split_once(".google.").map(|(_, suffix)| suffix));
+
+    suffix.is_some_and(|suffix| match suffix.split_once('.') {
+        None => suffix == "com" || suffix.len() == 2,
+        Some((second, country)) => {
+            ["com", "co"].contains(&second) && country.len() == 2
+        }
+    })
+}
+
+pub(crate) fn is_on_domain(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|subdomain| subdomain.ends_with('.'))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn clean(href: &str) -> String {
+        UrlCleaner::default().clean(href).unwrap()
+    }
+
+    #[test]
+    fn test_unwraps_relative_google_redirect() {
+        assert_eq!(
+            clean("/url?q=https://www.foxsports.com/nfl/david-blough&sa=U&ved=2ahUKEwj91bWk2IT3AhV1omoFHTGiCBgQFnoECAwQAg&usg=AOvVaw0tLu83JeMGMgnFF9iLD2uA"),
+            "https://www.foxsports.com/nfl/david-blough"
+        );
+    }
+
+    #[test]
+    fn test_unwraps_resolved_google_redirect() {
+        let input = "https://www.google.co.uk/url?q=https://www.sbnation.com/authors/jon-bois&sa=U&ved=2ahUKEwj91bWk2IT3AhV1omoFHTGiCBgQFnoECAwQAg&usg=AOvVaw0tLu83JeMGMgnFF9iLD2uA";
+
+        assert_eq!(clean(input), "https://www.sbnation.com/authors/jon-bois");
+    }
+
+    #[test]
+    fn test_keeps_redirects_on_other_hosts() {
+        for href in [
+            "https://evil.example/url?q=https://bank.example/",
+            "//evil.example/url?q=https://bank.example/",
+            "http://localhost:7878/url?q=https://bank.example/",
+            "https://notgoogle.com/url?q=https://bank.example/",
+            "https://google.com.evil.example/url?q=https://bank.example/",
+        ] {
+            assert!(clean(href).contains("/url?q="), "{}", href);
+        }
+    }
+
+    #[test]
+    fn test_is_google_host() {
+        assert!(is_google_host("google.com"));
+        assert!(is_google_host("www.google.com"));
+        assert!(is_google_host("www.google.co.uk"));
+        assert!(is_google_host("www.google.com.au"));
+        assert!(is_google_host("google.de"));
+        assert!(!is_google_host("evilgoogle.com"));
+        assert!(!is_google_host("www.google.evil.com"));
+    }
+
+    #[test]
+    fn test_unwraps_encoded_destination() {
+        assert_eq!(
+            clean("https://www.google.com/url?q=https://www.lowes.com/pl/Cordless--Drills/4294607722%3Frefinement%3D4294776932&sa=U"),
+            "https://www.lowes.com/pl/Cordless--Drills/4294607722?refinement=4294776932"
+        );
+    }
+
+    #[test]
+    fn test_unwraps_ad_click() {
+        assert_eq!(
+            clean("https://www.google.com/aclk?sa=l&ai=DChcSEwi&adurl=https://www.lowes.com/pl/drills%3Fgclid%3Dabc123"),
+            "https://www.lowes.com/pl/drills"
+        );
+    }
+
+    #[test]
+    fn test_unwraps_nested_trackers() {
+        assert_eq!(
+            clean("/url?q=https://l.facebook.com/l.php%3Fu%3Dhttps%253A%252F%252Fwww.mlb.com%252Fcubs%26h%3DAT0"),
+            "https://www.mlb.com/cubs"
+        );
+    }
+
+    #[test]
+    fn test_strips_tracking_params() {
+        assert_eq!(
+            clean("https://www.mlb.com/cubs/news?utm_source=google&utm_medium=cpc&id=7&fbclid=IwAR0"),
+            "https://www.mlb.com/cubs/news?id=7"
+        );
+    }
+
+    #[test]
+    fn test_keeps_encoding_of_kept_params() {
+        assert_eq!(
+            clean("https://example.com/search?q=a%20b+c&utm_source=x&p=%7E%2f"),
+            "https://example.com/search?q=a%20b+c&p=%7E%2f"
+        );
+        assert_eq!(
+            clean("https://example.com/?utm_source=x&gclid=y"),
+            "https://example.com/"
+        );
+    }
+
+    #[test]
+    fn test_keeps_untracked_urls() {
+        assert_eq!(
+            clean("https://en.wikipedia.org/wiki/David_Blough#2015_season"),
+            "https://en.wikipedia.org/wiki/David_Blough#2015_season"
+        );
+    }
+
+    #[test]
+    fn test_ignores_lookalike_hosts() {
+        assert_eq!(
+            clean("https://notfacebook.com/l.php?u=https://www.mlb.com/"),
+            "https://notfacebook.com/l.php?u=https://www.mlb.com/"
+        );
+    }
+
+    #[test]
+    fn test_custom_tracking_params() {
+        let cleaner = UrlCleaner::new(vec!["ref".to_string()]);
+
+        assert_eq!(
+            cleaner
+                .clean("https://www.mlb.com/cubs?ref=google&utm_source=x")
+                .unwrap(),
+            "https://www.mlb.com/cubs?utm_source=x"
+        );
+    }
+
+    #[test]
+    fn test_unparseable_urls() {
+        assert_eq!(UrlCleaner::default().clean("http://[::1"), None);
+    }
+}
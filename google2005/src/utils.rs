@@ -1 +1,5 @@
 pub mod google2005_error;
+pub mod fnv;
+pub mod hostnames;
+pub mod http_status;
+pub mod timestamp;
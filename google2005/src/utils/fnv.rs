@@ -0,0 +1,7 @@
+/// 32-bit FNV-1a, which unlike std's hasher gives the same hash on every
+/// release, so it can name things that outlive a build.
+pub fn fnv1a(text: &str) -> u32 {
+    text.bytes().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    })
+}
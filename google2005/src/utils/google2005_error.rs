@@ -1,62 +1,231 @@
 use askama;
-use serde::Serialize;
+use std::error::Error;
 use std::fmt::Display;
 use std::string::FromUtf8Error;
 
-#[derive(Debug, Serialize)]
-pub struct Google2005Error {
-    user_error: Option<String>,
-    internal_error: Option<String>,
-    pub status_code: u16,
-    pub status: String,
+use crate::utils::http_status;
+
+type BoxedError = Box<dyn Error + Send + Sync>;
+
+/// Everything that can go wrong between receiving a query and rendering its
+/// results. `Display` is the message for users; the internal details stay in
+/// `Debug` and the `source` chain, for logs.
+#[derive(Debug)]
+pub enum Google2005Error {
+    /// The query can't be searched; the message says why.
+    BadQuery(String),
+    /// The results page parsed, but nothing survived filtering.
+    NoResults,
+    /// The search engine couldn't be reached at all.
+    UpstreamUnavailable(BoxedError),
+    /// The search engine answered with a non-success status.
+    UpstreamStatus(u16),
+    /// The search engine served a captcha or "unusual traffic" page.
+    Blocked,
+    /// The results page didn't look like one we know how to read.
+    ParseFailed(String),
+    /// A template failed to render.
+    Render(askama::Error),
+    /// The search engine's response wasn't valid UTF-8.
+    Utf8(FromUtf8Error),
 }
 
 impl Google2005Error {
-    pub fn new(
-        user_error: Option<&str>,
-        internal_error: Option<&str>,
+    pub fn bad_query(message: &str) -> Google2005Error {
+        Google2005Error::BadQuery(message.to_string())
+    }
+
+    pub fn upstream_unavailable(
+        source: impl Into<BoxedError>,
     ) -> Google2005Error {
-        println!("creating error, user_error:     \n\n{:?}\n", user_error);
-        println!("                internal_error: \n\n{:?}\n", internal_error);
-
-        Google2005Error {
-            user_error: user_error.map(|s| s.to_string()),
-            internal_error: internal_error.map(|s| s.to_string()),
-            status_code: match user_error {
-                Some(_) => 400,
-                None => 500,
-            },
-            status: match user_error {
-                Some(_) => "Bad Request".to_string(),
-                None => "Internal Server Error".to_string(),
-            },
+        Google2005Error::UpstreamUnavailable(source.into())
+    }
+
+    pub fn parse_failed(reason: &str) -> Google2005Error {
+        Google2005Error::ParseFailed(reason.to_string())
+    }
+
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Google2005Error::BadQuery(_) => 400,
+            Google2005Error::NoResults => 404,
+            Google2005Error::UpstreamUnavailable(_)
+            | Google2005Error::UpstreamStatus(_)
+            | Google2005Error::ParseFailed(_)
+            | Google2005Error::Utf8(_) => 502,
+            Google2005Error::Blocked => 503,
+            Google2005Error::Render(_) => 500,
         }
     }
+
+    /// Reason phrase to go with `status_code`.
+    pub fn status(&self) -> &'static str {
+        http_status::reason_phrase(self.status_code())
+    }
+
+    /// A stable, machine-readable name for the variant.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Google2005Error::BadQuery(_) => "bad_query",
+            Google2005Error::NoResults => "no_results",
+            Google2005Error::UpstreamUnavailable(_) => "upstream_unavailable",
+            Google2005Error::UpstreamStatus(_) => "upstream_status",
+            Google2005Error::Blocked => "blocked",
+            Google2005Error::ParseFailed(_) => "parse_failed",
+            Google2005Error::Render(_) => "render",
+            Google2005Error::Utf8(_) => "utf8",
+        }
+    }
+
+    /// Whether the user is better served by running the same search on
+    /// google.com: true when the search engine failed us, false when the
+    /// query or our own rendering is at fault.
+    pub fn falls_back_to_google(&self) -> bool {
+        matches!(
+            self,
+            Google2005Error::UpstreamUnavailable(_)
+                | Google2005Error::UpstreamStatus(_)
+                | Google2005Error::Blocked
+                | Google2005Error::ParseFailed(_)
+                | Google2005Error::Utf8(_)
+        )
+    }
+
+    /// The internal description of what went wrong, including its causes.
+    /// Meant for logs, never for responses.
+    pub fn details(&self) -> String {
+        let mut details = match self {
+            Google2005Error::BadQuery(message) => {
+                format!("bad query: {}", message)
+            }
+            Google2005Error::NoResults => {
+                "no results survived filtering".into()
+            }
+            Google2005Error::UpstreamUnavailable(_) => {
+                "could not reach the search engine".into()
+            }
+            Google2005Error::UpstreamStatus(status) => {
+                format!("search engine responded with status {}", status)
+            }
+            Google2005Error::Blocked => {
+                "search engine served a captcha page".into()
+            }
+            Google2005Error::ParseFailed(reason) => {
+                format!("could not parse results page: {}", reason)
+            }
+            Google2005Error::Render(_) => "could not render template".into(),
+            Google2005Error::Utf8(_) => "response was not valid UTF-8".into(),
+        };
+
+        let mut source = self.source();
+        while let Some(cause) = source {
+            details.push_str(&format!(": {}", cause));
+            source = cause.source();
+        }
+
+        details
+    }
 }
 
 impl Display for Google2005Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self.user_error {
-            Some(e) => write!(f, "{}", e),
-            None => write!(f, "An internal error occurred, please try again"),
+        match self {
+            Google2005Error::BadQuery(message) => write!(f, "{}", message),
+            Google2005Error::NoResults => {
+                write!(f, "Your search did not match any documents")
+            }
+            Google2005Error::Blocked => write!(
+                f,
+                "Google is refusing our searches, please try again later"
+            ),
+            _ => write!(f, "An internal error occurred, please try again"),
+        }
+    }
+}
+
+impl Error for Google2005Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Google2005Error::UpstreamUnavailable(e) => Some(e.as_ref()),
+            Google2005Error::Render(e) => Some(e),
+            Google2005Error::Utf8(e) => Some(e),
+            _ => None,
         }
     }
 }
 
 impl From<askama::Error> for Google2005Error {
     fn from(e: askama::Error) -> Google2005Error {
-        Google2005Error::new(None, Some(&e.to_string()))
+        Google2005Error::Render(e)
     }
 }
 
+#[cfg(feature = "fastly")]
 impl From<fastly::http::request::SendError> for Google2005Error {
     fn from(e: fastly::http::request::SendError) -> Google2005Error {
-        Google2005Error::new(None, Some(&e.to_string()))
+        Google2005Error::upstream_unavailable(e)
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl From<reqwest::Error> for Google2005Error {
+    fn from(e: reqwest::Error) -> Google2005Error {
+        match e.status() {
+            Some(status) => Google2005Error::UpstreamStatus(status.as_u16()),
+            None => Google2005Error::upstream_unavailable(e),
+        }
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl From<hyper::Error> for Google2005Error {
+    fn from(e: hyper::Error) -> Google2005Error {
+        Google2005Error::upstream_unavailable(e)
     }
 }
 
 impl From<FromUtf8Error> for Google2005Error {
     fn from(e: FromUtf8Error) -> Google2005Error {
-        Google2005Error::new(None, Some(&e.to_string()))
+        Google2005Error::Utf8(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_user_message_hides_internal_details() {
+        let e = Google2005Error::parse_failed("results page has no body");
+
+        assert_eq!(e.status_code(), 502);
+        assert_eq!(
+            e.to_string(),
+            "An internal error occurred, please try again"
+        );
+        assert_eq!(
+            e.details(),
+            "could not parse results page: results page has no body"
+        );
+    }
+
+    #[test]
+    fn test_details_follow_source_chain() {
+        let bytes = vec![0xff, 0xfe];
+        let e = Google2005Error::from(String::from_utf8(bytes).unwrap_err());
+
+        assert!(e.source().is_some());
+        assert!(e
+            .details()
+            .starts_with("response was not valid UTF-8: invalid utf-8"));
+    }
+
+    #[test]
+    fn test_fall_back_to_google_only_for_upstream_failures() {
+        assert!(Google2005Error::Blocked.falls_back_to_google());
+        assert!(Google2005Error::UpstreamStatus(500).falls_back_to_google());
+        assert!(!Google2005Error::bad_query("Please enter a query")
+            .falls_back_to_google());
+        assert!(!Google2005Error::NoResults.falls_back_to_google());
     }
 }
@@ -0,0 +1,13 @@
+// Where the deployed pages and their static assets live.
+
+pub fn site_hostname() -> String {
+    "https://sensibly-stunning-blowfish.edgecompute.app/".to_string()
+}
+
+pub fn image_hostname() -> String {
+    "https://google2005.s3.us-east-2.amazonaws.com/images/".to_string()
+}
+
+pub fn stylesheet_hostname() -> String {
+    "https://google2005.s3.us-east-2.amazonaws.com/stylesheets/".to_string()
+}
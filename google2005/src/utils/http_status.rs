@@ -0,0 +1,13 @@
+/// The standard reason phrase for the status codes we send.
+pub fn reason_phrase(status_code: u16) -> &'static str {
+    match status_code {
+        200 => "OK",
+        302 => "Found",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
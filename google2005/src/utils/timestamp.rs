@@ -0,0 +1,71 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Formats `time` as an RFC 3339 UTC timestamp, e.g.
+/// `2022-06-08T21:11:50Z`, as required by Atom's `<updated>`.
+pub fn rfc3339(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0);
+
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let seconds_today = seconds % 86_400;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_today / 3600,
+        (seconds_today % 3600) / 60,
+        seconds_today % 60
+    )
+}
+
+// Howard Hinnant's days-to-civil algorithm for the proleptic Gregorian
+// calendar: http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524
+        - day_of_era / 146_096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_rfc3339_epoch() {
+        assert_eq!(rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn test_rfc3339() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_654_722_710);
+
+        assert_eq!(rfc3339(time), "2022-06-08T21:11:50Z");
+    }
+
+    #[test]
+    fn test_rfc3339_leap_day() {
+        let time = UNIX_EPOCH + Duration::from_secs(951_782_400);
+
+        assert_eq!(rfc3339(time), "2000-02-29T00:00:00Z");
+    }
+}
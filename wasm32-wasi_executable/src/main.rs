@@ -1,41 +1,91 @@
-use fastly::http::{header, Method, StatusCode};
-use fastly::{mime, Error, Request, Response};
+use fastly::{ConfigStore, Error, Request, Response};
 extern crate google2005;
-use google2005::home_page_response::HomePageResponse;
+use google2005::{
+    FastlyBackendFetcher, FetcherConfig, FilterRules, HttpRequest,
+    HttpResponse, Router,
+};
 
-use askama::Template;
-mod utils;
-
-use crate::utils::response::Response as Google2005Response;
+// Config store entries: `fetcher` is `direct` or `lambda` (the default),
+// `backend` names the Fastly backend to send searches to, and
+// `filter_rules` replaces the default filter rules with a JSON rule list.
+const CONFIG_STORE: &str = "google2005";
+const DEFAULT_BACKEND: &str = "google";
 
 #[fastly::main]
-fn main(req: Request) -> Result<Response, Error> {
-    match req.get_method() {
-        &Method::GET | &Method::HEAD => (),
-        _ => {
-            return Ok(Response::from_status(StatusCode::METHOD_NOT_ALLOWED)
-                .with_header(header::ALLOW, "GET, HEAD")
-                .with_body_text_plain("This method is not allowed\n"))
+fn main(mut req: Request) -> Result<Response, Error> {
+    let config = ConfigStore::try_open(CONFIG_STORE).ok();
+    let setting = |key: &str| config.as_ref().and_then(|c| c.get(key));
+
+    let fetcher = fetcher(setting("fetcher"), setting("backend"));
+    let router = Router::new(&fetcher)
+        .with_filter_rules(filter_rules(setting("filter_rules")));
+
+    let body = req.take_body_bytes();
+    let request = from_fastly(&req).with_body(body);
+
+    Ok(into_fastly(router.handle(&request)))
+}
+
+fn fetcher(
+    fetcher: Option<String>,
+    backend: Option<String>,
+) -> FastlyBackendFetcher {
+    let backend = backend.as_deref().unwrap_or(DEFAULT_BACKEND);
+
+    match fetcher.as_deref().and_then(FetcherConfig::parse) {
+        Some(FetcherConfig::Direct) => FastlyBackendFetcher::direct(backend),
+        Some(
+            FetcherConfig::File(path)
+            | FetcherConfig::Record(path)
+            | FetcherConfig::Replay(path),
+        ) => {
+            println!("No filesystem for {:?} on Fastly, using lambda", path);
+
+            FastlyBackendFetcher::lambda(backend)
         }
+        Some(FetcherConfig::Lambda) | None => {
+            FastlyBackendFetcher::lambda(backend)
+        }
+    }
+}
+
+fn filter_rules(json: Option<String>) -> FilterRules {
+    let json = match json {
+        Some(json) => json,
+        None => return FilterRules::default(),
+    };
+
+    FilterRules::from_json(&json).unwrap_or_else(|e| {
+        println!("Invalid filter_rules, using the defaults: {}", e);
+
+        FilterRules::default()
+    })
+}
+
+fn from_fastly(req: &Request) -> HttpRequest {
+    let target = match req.get_query_str() {
+        Some(query) => format!("{}?{}", req.get_path(), query),
+        None => req.get_path().to_string(),
     };
 
-    if req.get_path() == "/" {
-        let homepage = HomePageResponse::new().render().unwrap();
-
-        Ok(Response::from_status(StatusCode::OK)
-            .with_content_type(mime::TEXT_HTML_UTF_8)
-            .with_body(homepage))
-    } else if req.get_path() == "/search" {
-        Ok(Google2005Response::new(req.get_query_str()).render())
-    } else {
-        Ok(
-            Response::from_status(StatusCode::NOT_FOUND).with_body_text_plain(
-                &format!(
-                    r#"The requested page: "{}", with path: {}, could not be found\n"#,
-                    req.get_url_str(),
-                    req.get_path()
-                ),
-            ),
+    req.get_headers().fold(
+        HttpRequest::new(req.get_method_str(), &target),
+        |request, (name, value)| match value.to_str() {
+            Ok(value) => request.with_header(name.as_str(), value),
+            Err(_) => request,
+        },
+    )
+}
+
+fn into_fastly(response: HttpResponse) -> Response {
+    response
+        .headers
+        .iter()
+        .fold(
+            Response::from_status(response.status),
+            |fastly_response, (name, value)| {
+                fastly_response.with_header(name.as_str(), value.as_str())
+            },
         )
-    }
+        .with_body(response.body)
 }
@@ -1,135 +1,184 @@
-use chunked_transfer::Encoder;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
 
-mod utils;
+use hyper::body::{Bytes, HttpBody};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server};
 
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpListener, TcpStream};
+extern crate google2005;
+use google2005::{
+    FetcherConfig, FileFetcher, FilterRules, HttpFetcher, HttpRequest,
+    HttpResponse, LambdaFetcher, RecordingFetcher, ReplayFetcher, Router,
+    SearchFetcher, HEALTH_HEADER,
+};
+
+const LOCAL_HOSTNAME: &str = "http://127.0.0.1:7878/";
+// Bytes per chunk of a streamed response body.
+const CHUNK_SIZE: usize = 8 * 1024;
+// The longest request body read, plenty for the preferences form.
+const MAX_BODY_SIZE: usize = 64 * 1024;
 
-use std::fs;
-use std::fs::File;
-use std::io::BufRead;
-use std::io::Read;
-use std::io::Write;
+#[tokio::main]
+async fn main() {
+    let address = SocketAddr::from(([127, 0, 0, 1], 7878));
 
-use askama::Template;
+    let fetcher = fetcher(FetcherConfig::from_env());
+    let filter_rules = Arc::new(FilterRules::from_env());
 
-use crate::utils::response::Response as Google2005Response;
+    let make_service = make_service_fn(move |_connection| {
+        let fetcher = fetcher.clone();
+        let filter_rules = filter_rules.clone();
 
-extern crate google2005;
-use google2005::home_page_response::HomePageResponse;
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                handle(req, fetcher.clone(), filter_rules.clone())
+            }))
+        }
+    });
 
-#[tokio::main]
-async fn main() {
-    let listener: tokio::net::TcpListener =
-        TcpListener::bind("127.0.0.1:7878").await.unwrap();
     println!("listening on port 7878");
 
-    loop {
-        let (stream, _) = listener.accept().await.unwrap();
+    if let Err(e) = Server::bind(&address).serve(make_service).await {
+        println!("server error: {}", e);
+    }
+}
 
-        tokio::spawn(async move {
-            handle_connection(stream).await;
-        });
+// Searches Google directly unless `GOOGLE2005_FETCHER` says otherwise.
+fn fetcher(
+    config: Option<FetcherConfig>,
+) -> Arc<dyn SearchFetcher + Send + Sync> {
+    match config.unwrap_or(FetcherConfig::Direct) {
+        FetcherConfig::Direct => Arc::new(HttpFetcher::new()),
+        FetcherConfig::Lambda => Arc::new(LambdaFetcher::new()),
+        FetcherConfig::File(path) => Arc::new(FileFetcher::new(path)),
+        FetcherConfig::Record(dir) => {
+            Arc::new(RecordingFetcher::new(HttpFetcher::new(), dir))
+        }
+        FetcherConfig::Replay(dir) => Arc::new(ReplayFetcher::new(dir)),
     }
 }
 
-async fn handle_connection(mut stream: TcpStream) {
-    println!("handling connection");
-    let mut buffer = [0; 512];
-    stream.read(&mut buffer).await.unwrap();
-
-    println!("\nRequest: {}\n", String::from_utf8_lossy(&buffer[..]));
-
-    let response = if uri(&buffer).ends_with("css") {
-        println!("fetching css");
-        let css = fs::read_to_string(
-            "../google2005/src/client/stylesheets/search.css",
-        )
-        .unwrap();
-
-        render_static(&css)
-    } else if uri(&buffer).ends_with("png") {
-        let path = match uri(&buffer).split("/").last().unwrap() {
-            "two.png" => "src/client/images/two.png",
-            "betteryellowzero.png" => "src/client/images/betteryellowzero.png",
-            "betterredzero.png" => "src/client/images/betterredzero.png",
-            "five.png" => "src/client/images/five.png",
-            "logo.png" => "src/client/images/logo.png",
-            _ => "src/client/images/two.png",
-        };
-
-        render_image(path)
-    } else if uri(&buffer) == "/" {
-        let homepage = HomePageResponse::new().render().unwrap();
-        render_static(&homepage)
-    } else {
-        Google2005Response::new(&buffer)
-            .await
-            .render()
-            .as_bytes()
-            .to_vec()
+async fn handle(
+    req: Request<Body>,
+    fetcher: Arc<dyn SearchFetcher + Send + Sync>,
+    filter_rules: Arc<FilterRules>,
+) -> Result<Response<Body>, Infallible> {
+    println!("{} {}", req.method(), req.uri());
+
+    let head = req.method() == Method::HEAD;
+    let request = from_hyper(&req);
+    let request = match read_body(req.into_body()).await {
+        Some(body) => request.with_body(body),
+        None => {
+            let response = HttpResponse::new(413).with_body(
+                "text/plain; charset=UTF-8",
+                "The request body is too long\n",
+            );
+
+            return Ok(into_hyper(response, head));
+        }
     };
 
-    stream.write(&response).await.unwrap();
-    stream.flush().await.unwrap();
+    // The router fetches from Google synchronously. Hyper leaves out the
+    // body of HEAD responses itself, after counting it for Content-Length.
+    let response = tokio::task::spawn_blocking(move || {
+        Router::new(fetcher.as_ref())
+            .with_site_hostname(LOCAL_HOSTNAME)
+            .with_filter_rules(filter_rules)
+            .respond(&request)
+    })
+    .await
+    .unwrap_or_else(|e| {
+        println!("request handler panicked: {}", e);
+
+        HttpResponse::new(500)
+    });
+
+    if let Some(health) = response.header(HEALTH_HEADER) {
+        if !health.starts_with("ok") {
+            println!("{}: {}", HEALTH_HEADER, health);
+        }
+    }
 
-    println!(
-        "\n---------------------------------------------------------------\n\n"
-    );
+    Ok(into_hyper(response, head))
 }
 
-pub fn render_static(contents: &str) -> Vec<u8> {
-    format!(
-        "{}\r\nContent-Length: {}\r\n\r\n{}",
-        "HTTP/1.1 200 OK".to_string(),
-        contents.len(),
-        contents
+fn from_hyper(req: &Request<Body>) -> HttpRequest {
+    let target = req
+        .uri()
+        .path_and_query()
+        .map(|target| target.as_str())
+        .unwrap_or("/");
+
+    req.headers().iter().fold(
+        HttpRequest::new(req.method().as_str(), target),
+        |request, (name, value)| match value.to_str() {
+            Ok(value) => request.with_header(name.as_str(), value),
+            Err(_) => request,
+        },
     )
-    .as_bytes()
-    .to_vec()
 }
 
-fn render_image(path: &str) -> Vec<u8> {
-    let mut buf = Vec::new();
-    let mut file = File::open(&path).unwrap();
-    file.read_to_end(&mut buf).unwrap();
-
-    let mut encoded = Vec::new();
-    {
-        let mut encoder = Encoder::with_chunks_size(&mut encoded, 32);
-        encoder.write_all(&buf).unwrap();
+// The whole body, or None when it's longer than `MAX_BODY_SIZE` or the
+// client stops sending it.
+async fn read_body(mut body: Body) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.ok()?;
+        if bytes.len() + chunk.len() > MAX_BODY_SIZE {
+            return None;
+        }
+        bytes.extend_from_slice(&chunk);
     }
 
-    let headers = [
-        "HTTP/1.1 200 OK",
-        "Content-type: image/jpeg",
-        "Transfer-Encoding: chunked",
-        "\r\n",
-    ];
-
-    let mut response = headers.join("\r\n").to_string().into_bytes();
-    response.extend(encoded);
-
-    response
+    Some(bytes)
 }
 
-fn first_line(buffer: &[u8]) -> String {
-    let mut lines = buffer.lines();
-    let first = lines.next().unwrap();
+// Sends GET bodies with chunked transfer encoding. HEAD responses keep the
+// whole body, so hyper can report its length.
+fn into_hyper(response: HttpResponse, head: bool) -> Response<Body> {
+    let body = if head {
+        Body::from(response.body)
+    } else {
+        streamed(response.body)
+    };
+    let builder = response.headers.iter().fold(
+        Response::builder().status(response.status),
+        |builder, (name, value)| builder.header(name, value),
+    );
 
-    match first {
-        Ok(s) => s,
-        Err(_) => "".to_string(),
-    }
-}
+    builder
+        .header("Server", "Google2005")
+        .body(body)
+        .unwrap_or_else(|e| {
+            println!("could not build response: {}", e);
 
-fn uri(buffer: &[u8]) -> String {
-    let first_line = first_line(buffer);
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = hyper::StatusCode::INTERNAL_SERVER_ERROR;
 
-    let mut parts = first_line.split_whitespace();
+            response
+        })
+}
 
-    match parts.next() {
-        Some(_s) => parts.next().unwrap_or("").to_string(),
-        None => "".to_string(),
-    }
+// Sends `body` to the client a chunk at a time. The router has already
+// rendered all of it, so this only frames it as chunks; nothing is sent
+// before the page is complete.
+fn streamed(body: Vec<u8>) -> Body {
+    let (mut sender, stream) = Body::channel();
+    let mut body = Bytes::from(body);
+
+    tokio::spawn(async move {
+        while !body.is_empty() {
+            let chunk = body.split_to(CHUNK_SIZE.min(body.len()));
+
+            if let Err(e) = sender.send_data(chunk).await {
+                println!("could not stream response: {}", e);
+                break;
+            }
+        }
+    });
+
+    stream
 }
@@ -1,2 +0,0 @@
-pub mod response;
-pub mod request;
@@ -1,7 +0,0 @@
-pub fn query(buffer: &[u8]) -> String {
-    let after_equals = &buffer[14..];
-    let until_space = after_equals.split(|c| *c == b' ').next().unwrap();
-    let string_query = String::from_utf8_lossy(until_space);
-
-    string_query.to_string()
-}